@@ -5,13 +5,90 @@
  */
 
 // --- Hasher ---
+export type HashAlgorithm =
+  | 'sha256'
+  | 'blake3'
+  | 'xxh3'
+  | 'gitSha1'
+  | 'gitSha256';
+
+export type SymlinkMode = 'follow' | 'hashTarget';
+
+export type DigestEncoding = 'hex' | 'base64Url' | 'buffer';
+
+export interface HashOptions {
+  algorithm?: HashAlgorithm;
+  useMmap?: boolean;
+  symlinks?: SymlinkMode;
+  includeStat?: boolean;
+  cachePath?: string;
+  normalizeLineEndings?: boolean;
+  sampleSize?: number;
+  readAhead?: boolean;
+  maxConcurrency?: number;
+  maxBytesPerSecond?: number;
+  multihash?: boolean;
+  encoding?: DigestEncoding;
+}
+export function initHashKey(secret: Buffer): void;
 export function sha256Hash(content: string): string;
-export function sha256HashFile(filePath: string): string;
+export function sha256HashBuffer(content: Buffer): string;
+export function sha256HashBuffers(contents: Buffer[]): string[];
+export function sha256HashFile(filePath: string, options?: HashOptions): string;
 export interface FileHash {
   path: string;
   hash: string;
+  symlinkTarget?: string;
+  brokenSymlink?: boolean;
+  sampled?: boolean;
+  digest?: Buffer;
+  size?: number;
+  mtime?: number;
+}
+export function sha256HashFiles(
+  filePaths: string[],
+  options?: HashOptions
+): HashFilesResult;
+export function hashString(content: string, algorithm?: HashAlgorithm): string;
+export function hashBuffer(content: Buffer, algorithm?: HashAlgorithm): string;
+export class Hasher {
+  constructor(algorithm?: HashAlgorithm, contentLength?: number);
+  update(data: Buffer): void;
+  digestHex(): string;
+}
+export function hashFile(filePath: string, options?: HashOptions): string;
+export type HashErrorKind =
+  | 'notFound'
+  | 'permissionDenied'
+  | 'brokenSymlink'
+  | 'isDirectory'
+  | 'other';
+export interface HashFailure {
+  path: string;
+  errorKind: HashErrorKind;
+  message: string;
+}
+export interface HashFilesResult {
+  hashes: FileHash[];
+  failed: HashFailure[];
+}
+export function hashFiles(
+  filePaths: string[],
+  options?: HashOptions
+): HashFilesResult;
+export function hashFilesCombined(
+  filePaths: string[],
+  options?: HashOptions
+): string;
+export interface StaleEntries {
+  modified: string[];
+  missing: string[];
 }
-export function sha256HashFiles(filePaths: string[]): FileHash[];
+export function findStaleEntries(
+  rootPath: string,
+  entries: FileHashEntry[],
+  options?: HashOptions
+): StaleEntries;
 
 // --- Merkle Tree ---
 export interface MerkleNode {
@@ -19,32 +96,608 @@ export interface MerkleNode {
   hash: string;
   isFile: boolean;
   children: string[];
+  algorithm?: HashAlgorithm;
+  isRoot?: boolean;
+  size?: number;
+  fileCount?: number;
+  hashScheme?: number;
+  namespace?: string;
 }
 
 export interface MerkleDiff {
   added: string[];
   removed: string[];
   modified: string[];
+  changedDirectories?: string[];
+  renamed?: FileRename[];
+}
+export interface FileRename {
+  from: string;
+  to: string;
+  hash: string;
 }
 
 export interface FileHashEntry {
   path: string;
+  /** Ignored for directory entries */
   hash: string;
+  /** Declares `path` as a directory, so it appears in the tree even when empty */
+  isDirectory?: boolean;
+  size?: number;
+}
+
+export interface MerkleOptions {
+  algorithm?: HashAlgorithm;
+  /** 1 (default): hash of sorted child hashes; 2: domain-separated, covers child names */
+  hashScheme?: number;
+  /** Salt mixed into every directory hash, e.g. a workspace id; file hashes are unchanged */
+  namespace?: string;
 }
 
-export function buildMerkleTree(fileHashes: FileHashEntry[]): MerkleNode[];
+export function buildMerkleTree(
+  fileHashes: FileHashEntry[],
+  options?: MerkleOptions
+): MerkleNode[];
+export interface DiffOptions {
+  includeDirectories?: boolean;
+  detectRenames?: boolean;
+}
 export function diffMerkleTrees(
   oldNodes: MerkleNode[],
-  newNodes: MerkleNode[]
+  newNodes: MerkleNode[],
+  options?: DiffOptions
 ): MerkleDiff;
+export interface DiffEntry {
+  path: string;
+  oldHash?: string;
+  newHash?: string;
+}
+export interface DetailedMerkleDiff {
+  added: DiffEntry[];
+  removed: DiffEntry[];
+  modified: DiffEntry[];
+  changedDirectories?: string[];
+  renamed?: FileRename[];
+}
+export function diffMerkleTreesDetailed(
+  oldNodes: MerkleNode[],
+  newNodes: MerkleNode[],
+  options?: DiffOptions
+): DetailedMerkleDiff;
+export type ChangeKind = 'added' | 'removed' | 'modified';
+export interface DiffEvent {
+  path: string;
+  kind: ChangeKind;
+  oldHash?: string;
+  newHash?: string;
+}
+export interface StreamingDiffOptions {
+  batchSize?: number;
+}
+export interface StreamingDiffSummary {
+  emitted: number;
+  completed: boolean;
+}
+/** Return `false` from the callback to stop early */
+export function diffMerkleTreesStreaming(
+  oldNodes: MerkleNode[],
+  newNodes: MerkleNode[],
+  callback: (events: DiffEvent[]) => boolean | void,
+  options?: StreamingDiffOptions
+): StreamingDiffSummary;
+export type MergeStatus = 'localOnly' | 'remoteOnly' | 'converged' | 'conflict';
+export interface MergeEntry {
+  path: string;
+  status: MergeStatus;
+  /** Absent hashes mean the file does not exist on that side */
+  baseHash?: string;
+  localHash?: string;
+  remoteHash?: string;
+}
+export interface ThreeWayDiff {
+  entries: MergeEntry[];
+  unchanged: number;
+}
+export function diff3(
+  baseNodes: MerkleNode[],
+  localNodes: MerkleNode[],
+  remoteNodes: MerkleNode[]
+): ThreeWayDiff;
 export function getRootHash(nodes: MerkleNode[]): string | null;
+export interface TreeStats {
+  fileCount: number;
+  directoryCount: number;
+  maxDepth: number;
+  totalBytes?: number;
+  algorithm: HashAlgorithm;
+  hashScheme: number;
+  namespace?: string;
+}
+export function getTreeStats(nodes: MerkleNode[]): TreeStats;
+export function extractSubtree(nodes: MerkleNode[], prefix: string): MerkleNode[];
+export interface NamedTree {
+  name: string;
+  nodes: MerkleNode[];
+}
+export function mergeTrees(roots: NamedTree[]): MerkleNode[];
+export interface TreeChange {
+  path: string;
+  hash?: string;
+  deleted?: boolean;
+  /** Declares `path` as a directory, kept even when the update empties it */
+  isDirectory?: boolean;
+  size?: number;
+}
+export interface UpdatedTree {
+  /** In no particular order */
+  nodes: MerkleNode[];
+  rootHash?: string;
+}
+export interface ProofSibling {
+  name: string;
+  hash: string;
+  isFile: boolean;
+}
+export interface MerkleProofStep {
+  directory: string;
+  siblingHashes: string[];
+  siblings?: ProofSibling[];
+}
+export interface MerkleProof {
+  algorithm: HashAlgorithm;
+  hashScheme?: number;
+  namespace?: string;
+  steps: MerkleProofStep[];
+}
+export function getMerkleProof(nodes: MerkleNode[], path: string): MerkleProof;
+export function verifyMerkleProof(
+  rootHash: string,
+  path: string,
+  fileHash: string,
+  proof: MerkleProof
+): boolean;
+export function serializeTree(nodes: MerkleNode[]): Buffer;
+export function deserializeTree(data: Buffer): MerkleNode[];
+export function exportTreeJson(nodes: MerkleNode[]): string;
+export function importTreeJson(json: string): MerkleNode[];
+export function saveTree(filePath: string, nodes: MerkleNode[]): void;
+export class MappedTree {
+  readonly nodeCount: number;
+  rootHash(): string | null;
+  get(path: string): MerkleNode | null;
+  hashOf(path: string): string | null;
+  diff(newNodes: MerkleNode[], options?: DiffOptions): MerkleDiff;
+  toNodes(): MerkleNode[];
+}
+export function openTree(filePath: string): MappedTree;
+export class MerkleTree {
+  constructor(nodes: MerkleNode[]);
+  static build(fileHashes: FileHashEntry[], options?: MerkleOptions): MerkleTree;
+  static deserialize(data: Buffer): MerkleTree;
+  readonly nodeCount: number;
+  rootHash(): string | null;
+  getNode(path: string): MerkleNode | null;
+  children(path: string): MerkleNode[];
+  /** This tree is the old side */
+  diff(other: MerkleTree, options?: DiffOptions): MerkleDiff;
+  /** Updates the tree in place; returns the new root hash */
+  applyChanges(changes: TreeChange[]): string | null;
+  serialize(): Buffer;
+  toNodes(): MerkleNode[];
+}
+export function applyChanges(
+  nodes: MerkleNode[],
+  changes: TreeChange[]
+): UpdatedTree;
+export interface HashDirectoryOptions {
+  scan?: ScanOptions;
+  hash?: HashOptions;
+  hashScheme?: number;
+  namespace?: string;
+}
+export interface DirectoryDigest {
+  rootHash: string;
+  fileCount: number;
+}
+export function hashDirectory(
+  rootPath: string,
+  options?: HashDirectoryOptions
+): DirectoryDigest;
+export function buildMerkleTreeFromDirectory(
+  rootPath: string,
+  options?: HashDirectoryOptions
+): MerkleNode[];
+export function buildSerializedMerkleTreeFromDirectory(
+  rootPath: string,
+  options?: HashDirectoryOptions
+): Buffer;
 
 // --- Scanner ---
+export type ScanOrder = 'lexicographic' | 'directoriesFirst';
+
+export interface ScanOptions {
+  extensions?: string[];
+  maxEntriesPerDir?: number;
+  order?: ScanOrder;
+  maxFileSize?: number;
+  skipBinary?: boolean;
+  countIgnored?: boolean;
+  /** File names such as `.noindex` that exclude the directory holding them */
+  stopFiles?: string[];
+}
+
+export interface SkippedDirectory {
+  path: string;
+  entryCount: number;
+}
+
+export interface ScanError {
+  path?: string;
+  message: string;
+}
+
+export interface ScanReport {
+  directoriesVisited: number;
+  filesMatched: number;
+  ignoredByGitignore: number;
+  ignoredHidden: number;
+  ignoredByExtension: number;
+  ignoredBySize: number;
+  ignoredBinary: number;
+  errors: ScanError[];
+  elapsedMs: number;
+}
+
+export interface ScanResult {
+  files: string[];
+  skippedDirectories: SkippedDirectory[];
+  stoppedDirectories: string[];
+  report: ScanReport;
+}
+
 export function scanDirectory(
   rootPath: string,
   extensions: string[]
 ): string[];
+export function scanDirectoryWithOptions(
+  rootPath: string,
+  options?: ScanOptions
+): ScanResult;
+/** Path of the stop file excluding `filePath`, checking `rootPath` down to its parent */
+export function findStopFile(
+  rootPath: string,
+  filePath: string,
+  stopFiles: string[]
+): string | null;
+export interface ScannedFile {
+  path: string;
+  hash: string;
+  size: number;
+  mtime: number;
+}
+export interface ScanAndHashResult {
+  files: ScannedFile[];
+  failed: HashFailure[];
+}
+export function scanAndHash(
+  rootPath: string,
+  options?: ScanOptions,
+  hashOptions?: HashOptions
+): ScanAndHashResult;
 export function getRelativePath(
   rootPath: string,
   filePath: string
 ): string | null;
+
+// --- Analysis ---
+export interface SizeAnalysisOptions {
+  scan?: ScanOptions;
+  topN?: number;
+  bucketBoundaries?: number[];
+}
+
+export interface SizeBucket {
+  minBytes: number;
+  maxBytes?: number;
+  fileCount: number;
+  totalBytes: number;
+}
+
+export interface FileSize {
+  path: string;
+  size: number;
+  share: number;
+}
+
+export interface DirectorySize {
+  path: string;
+  fileCount: number;
+  totalBytes: number;
+  share: number;
+}
+
+export interface SizeReport {
+  totalFiles: number;
+  totalBytes: number;
+  buckets: SizeBucket[];
+  largest: FileSize[];
+  largestDirectories: DirectorySize[];
+}
+
+export function analyzeWorkspaceSizes(
+  rootPath: string,
+  options?: SizeAnalysisOptions
+): SizeReport;
+
+// --- Content-defined chunking ---
+export interface CdcOptions {
+  min?: number;
+  avg?: number;
+  max?: number;
+  algorithm?: HashAlgorithm;
+}
+
+export interface CdcChunk {
+  offset: number;
+  length: number;
+  hash: string;
+}
+
+export function chunkFileCdc(
+  filePath: string,
+  options?: CdcOptions
+): CdcChunk[];
+
+// --- Self-test ---
+export interface SelfTestStage {
+  name: string;
+  passed: boolean;
+  elapsedMs: number;
+  error?: string;
+}
+
+export interface SelfTestReport {
+  passed: boolean;
+  stages: SelfTestStage[];
+}
+
+export function runSelfTest(): SelfTestReport;
+
+// --- Crash capture ---
+export interface CrashReport {
+  message: string;
+  location?: string;
+  thread?: string;
+  crashFile?: string;
+  timestamp: number;
+  recentEvents: string[];
+}
+export interface CrashHandlerOptions {
+  redactPaths?: string[];
+}
+
+export function installCrashHandler(
+  crashDir: string,
+  options?: CrashHandlerOptions
+): void;
+export function recordTraceEvent(event: string): void;
+export function takeLastCrash(): CrashReport | null;
+
+// --- Compression ---
+export function compressZstd(
+  data: Buffer,
+  level?: number,
+  dictionary?: Buffer
+): Buffer;
+export function decompressZstd(
+  data: Buffer,
+  dictionary?: Buffer,
+  maxOutputSize?: number
+): Buffer;
+export function trainZstdDictionary(samples: Buffer[], maxSize?: number): Buffer;
+
+// --- Version ---
+export interface NativeVersion {
+  version: string;
+  apiLevel: number;
+  target: string;
+}
+
+export interface FeatureFlags {
+  cdc: boolean;
+  zstd: boolean;
+  readAhead: boolean;
+}
+
+export function getNativeVersion(): NativeVersion;
+export function getFeatureFlags(): FeatureFlags;
+
+// --- Format compatibility ---
+export interface FixtureCheck {
+  format: string;
+  passed: boolean;
+  error?: string;
+}
+
+export function writeFormatFixtures(dir: string): string[];
+export function verifyFormatFixtures(dir: string): FixtureCheck[];
+
+// --- Packed results ---
+export function scanDirectoryPacked(
+  rootPath: string,
+  options?: ScanOptions
+): Buffer;
+export function chunkFileCdcPacked(
+  filePath: string,
+  options?: CdcOptions
+): Buffer;
+
+// --- Stat ---
+export interface PathStat {
+  path: string;
+  exists: boolean;
+  isFile?: boolean;
+  isDirectory?: boolean;
+  size?: number;
+  mtime?: number;
+  error?: string;
+}
+
+export function statPaths(paths: string[]): PathStat[];
+export type PathStatus = 'ok' | 'missing' | 'notFile' | 'unreadable';
+export interface PathValidation {
+  path: string;
+  status: PathStatus;
+  error?: string;
+}
+export function validatePaths(paths: string[], root?: string): PathValidation[];
+
+// --- File style ---
+export type LineEnding = 'none' | 'lf' | 'crlf' | 'mixed';
+export type IndentStyle = 'none' | 'tabs' | 'spaces';
+export interface FileStyle {
+  lineEnding: LineEnding;
+  indent: IndentStyle;
+  indentWidth?: number;
+  finalNewline: boolean;
+}
+
+export function detectFileStyle(filePath: string): FileStyle;
+export function clearFileStyleCache(): void;
+
+// --- Remote comparison ---
+export interface RemoteEntry {
+  path: string;
+  hash: string;
+  isFile: boolean;
+}
+
+export class RemoteComparison {
+  constructor(nodes: MerkleNode[], remoteRootHash?: string | null);
+  readonly isDone: boolean;
+  nextProbes(max?: number): string[];
+  supply(directory: string, children: RemoteEntry[]): void;
+  result(): MerkleDiff;
+}
+export interface RemoteCompareOptions {
+  remoteRootHash?: string;
+  concurrency?: number;
+}
+export function compareWithRemote(
+  nodes: MerkleNode[],
+  probe: (directory: string) => Promise<RemoteEntry[]> | RemoteEntry[],
+  options?: RemoteCompareOptions
+): Promise<MerkleDiff>;
+
+// --- Embeddings ---
+export interface SimilarityOptions {
+  topK?: number;
+}
+
+export interface RowMatches {
+  indices: number[];
+  scores: number[];
+}
+
+export interface SimilarityResult {
+  rows: number;
+  cols: number;
+  /** Row-major f32 scores; read with `new Float32Array(buf.buffer, buf.byteOffset, rows * cols)` */
+  scores?: Buffer;
+  top?: RowMatches[];
+}
+
+export function similarityMatrix(
+  a: Buffer,
+  b: Buffer,
+  dim: number,
+  options?: SimilarityOptions
+): SimilarityResult;
+
+export interface ClusterOptions {
+  /** Number of clusters; at most one of `k` and `threshold`, default √(n/2) clusters */
+  k?: number;
+  /** Minimum cosine similarity to a cluster's leader to join it */
+  threshold?: number;
+  maxIterations?: number;
+  /** Representatives per cluster, default 3 */
+  representatives?: number;
+  seed?: number;
+}
+
+export interface Cluster {
+  size: number;
+  /** Vector indices closest to the centroid, closest first */
+  representatives: number[];
+  /** Mean cosine similarity of the members to the centroid */
+  cohesion: number;
+}
+
+export interface ClusterResult {
+  /** Cluster index of every vector; clusters are numbered largest first */
+  assignments: number[];
+  clusters: Cluster[];
+  iterations: number;
+}
+
+export function clusterEmbeddings(
+  vectors: Buffer,
+  dim: number,
+  options?: ClusterOptions
+): ClusterResult;
+
+// --- Chunk quality ---
+export interface ChunkQualityOptions {
+  /** Default 16 KiB */
+  maxBytes?: number;
+  /** Longest line before a chunk counts as minified, default 1000 */
+  maxLineLength?: number;
+  /** Default 0.5 */
+  maxSymbolRatio?: number;
+  /** Default 0.5 */
+  maxEncodedRatio?: number;
+  /** Default 3 */
+  outlierZScore?: number;
+}
+
+export type ChunkIssue = 'tooLong' | 'longLines' | 'symbols' | 'encoded' | 'outlier';
+
+export interface FlaggedChunk {
+  index: number;
+  issues: ChunkIssue[];
+  symbolRatio: number;
+  encodedRatio: number;
+  centroidSimilarity?: number;
+}
+
+/** `embeddings`: one packed f32 vector per chunk, in chunk order */
+export function detectLowQualityChunks(
+  chunks: string[],
+  embeddings?: Buffer | null,
+  options?: ChunkQualityOptions
+): FlaggedChunk[];
+
+// --- Fingerprints ---
+export interface FingerprintOptions {
+  /** Bits set per file, default 3 */
+  hashCount?: number;
+}
+
+export interface DivergenceEstimate {
+  filesA: number;
+  filesB: number;
+  shared: number;
+  /** Files on one side only; a modified file counts once per side */
+  different: number;
+  /** Estimated Jaccard similarity of the file sets */
+  similarity: number;
+  saturated: boolean;
+}
+
+/** Bloom filter over the tree's (path, hash) file entries */
+export function treeFingerprint(
+  nodes: MerkleNode[],
+  bits: number,
+  options?: FingerprintOptions
+): Buffer;
+export function estimateDivergence(a: Buffer, b: Buffer): DivergenceEstimate;
@@ -0,0 +1,171 @@
+use crate::scanner::{relative_slash_path, walk, ScanOptions};
+use napi_derive::napi;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Default histogram boundaries: 1 KiB, 10 KiB, 100 KiB, 1 MiB, 10 MiB
+const DEFAULT_BUCKET_BOUNDARIES: [i64; 5] = [1 << 10, 10 << 10, 100 << 10, 1 << 20, 10 << 20];
+const DEFAULT_TOP_N: u32 = 20;
+
+/// Options for `analyze_workspace_sizes`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct SizeAnalysisOptions {
+    /// Which files to consider (same rules as `scan_directory_with_options`)
+    pub scan: Option<ScanOptions>,
+    /// Number of largest files and directories to return (default 20)
+    pub top_n: Option<u32>,
+    /// Ascending bucket boundaries in bytes (default 1K/10K/100K/1M/10M)
+    pub bucket_boundaries: Option<Vec<i64>>,
+}
+
+/// One histogram bucket covering `[min_bytes, max_bytes)`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SizeBucket {
+    pub min_bytes: i64,
+    /// Exclusive upper bound; absent for the last, open-ended bucket
+    pub max_bytes: Option<i64>,
+    pub file_count: u32,
+    pub total_bytes: i64,
+}
+
+/// A file and its size
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct FileSize {
+    /// Path relative to the scan root, `/`-separated
+    pub path: String,
+    pub size: i64,
+    /// Fraction of the workspace's total bytes
+    pub share: f64,
+}
+
+/// Total size of the files below a directory
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct DirectorySize {
+    /// Path relative to the scan root, `/`-separated
+    pub path: String,
+    pub file_count: u32,
+    pub total_bytes: i64,
+    /// Fraction of the workspace's total bytes
+    pub share: f64,
+}
+
+/// Result of `analyze_workspace_sizes`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SizeReport {
+    pub total_files: u32,
+    pub total_bytes: i64,
+    pub buckets: Vec<SizeBucket>,
+    /// Largest files, biggest first
+    pub largest: Vec<FileSize>,
+    /// Directories holding the most bytes, biggest first; nested directories are
+    /// listed separately, so a parent's total includes its children's
+    pub largest_directories: Vec<DirectorySize>,
+}
+
+/// Size histogram and the largest indexable files and directories of a workspace,
+/// for tuning size limits and finding candidates for exclusion
+#[napi(catch_unwind)]
+pub fn analyze_workspace_sizes(
+    root_path: String,
+    options: Option<SizeAnalysisOptions>,
+) -> napi::Result<SizeReport> {
+    use rayon::prelude::*;
+
+    let options = options.unwrap_or_default();
+    let scan = walk(&root_path, &options.scan.unwrap_or_default())?;
+    let root = Path::new(&root_path);
+
+    let mut boundaries = options
+        .bucket_boundaries
+        .unwrap_or_else(|| DEFAULT_BUCKET_BOUNDARIES.to_vec());
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut sizes: Vec<FileSize> = scan
+        .files
+        .par_iter()
+        .filter_map(|file| {
+            let path = Path::new(file);
+            Some(FileSize {
+                path: relative_slash_path(root, path)?,
+                size: fs::metadata(path).ok()?.len() as i64,
+                share: 0.0,
+            })
+        })
+        .collect();
+
+    let mut buckets: Vec<SizeBucket> = std::iter::once(0)
+        .chain(boundaries.iter().copied())
+        .zip(boundaries.iter().copied().map(Some).chain(std::iter::once(None)))
+        .map(|(min_bytes, max_bytes)| SizeBucket {
+            min_bytes,
+            max_bytes,
+            file_count: 0,
+            total_bytes: 0,
+        })
+        .collect();
+
+    for file in &sizes {
+        let index = boundaries.partition_point(|&b| b <= file.size);
+        buckets[index].file_count += 1;
+        buckets[index].total_bytes += file.size;
+    }
+
+    let mut directories: BTreeMap<&str, (u32, i64)> = BTreeMap::new();
+    for file in &sizes {
+        let mut path = file.path.as_str();
+        while let Some(index) = path.rfind('/') {
+            path = &path[..index];
+            let entry = directories.entry(path).or_default();
+            entry.0 += 1;
+            entry.1 += file.size;
+        }
+    }
+
+    let total_files = sizes.len() as u32;
+    let total_bytes: i64 = sizes.iter().map(|f| f.size).sum();
+    let share = |bytes: i64| {
+        if total_bytes > 0 {
+            bytes as f64 / total_bytes as f64
+        } else {
+            0.0
+        }
+    };
+    let top_n = options.top_n.unwrap_or(DEFAULT_TOP_N) as usize;
+
+    let mut largest_directories: Vec<DirectorySize> = directories
+        .into_iter()
+        .map(|(path, (file_count, bytes))| DirectorySize {
+            path: path.to_string(),
+            file_count,
+            total_bytes: bytes,
+            share: share(bytes),
+        })
+        .collect();
+    largest_directories.sort_by(|a, b| {
+        b.total_bytes
+            .cmp(&a.total_bytes)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    largest_directories.truncate(top_n);
+
+    sizes.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    sizes.truncate(top_n);
+    for file in &mut sizes {
+        file.share = share(file.size);
+    }
+
+    Ok(SizeReport {
+        total_files,
+        total_bytes,
+        buckets,
+        largest: sizes,
+        largest_directories,
+    })
+}
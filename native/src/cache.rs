@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// File magic and format version of the on-disk hash cache
+const MAGIC: &[u8; 4] = b"CNHC";
+/// Version 1 lacks the per-entry time of last use
+const FORMAT_VERSION_V1: u8 = 1;
+const FORMAT_VERSION: u8 = 2;
+
+/// Entries not stored or confirmed by a save for this long (ms) are dropped, so
+/// the paths of deleted and renamed files do not accumulate
+const STALE_AFTER_MS: f64 = 30.0 * 24.0 * 3600.0 * 1000.0;
+
+/// How long a save waits for another saver's lock before giving up, and the age
+/// after which a lock left behind by a crashed saver is broken
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const LOCK_STALE_AFTER: Duration = Duration::from_secs(30);
+
+/// Entries modified this recently (ms) are not cached: a second write within the
+/// filesystem's mtime granularity would otherwise go unnoticed
+const RACY_WINDOW_MS: f64 = 2000.0;
+
+/// Distinguishes temp files of concurrent saves within one process
+static SAVE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A cached hash, valid while the file's size and mtime are unchanged
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CacheEntry {
+    pub size: u64,
+    pub mtime: f64,
+    pub hash: String,
+}
+
+/// A cache entry with the time (ms since the Unix epoch) it was last stored
+#[derive(Clone, Debug)]
+struct StoredEntry {
+    entry: CacheEntry,
+    used_ms: f64,
+}
+
+/// Persistent map of path -> (size, mtime, hash).
+///
+/// All entries share one `variant` describing the hash settings (algorithm and
+/// anything else that changes the digest); a file written with another variant
+/// is ignored on load. Layout, all integers little-endian:
+///
+/// ```text
+/// "CNHC" u8:version u16:variant_len variant u32:count
+/// count x { u32:path_len path u64:size f64:mtime_ms f64:used_ms u8:hash_len hash }
+/// ```
+///
+/// Version 1 files lack `used_ms`; their entries count as used when loaded.
+pub(crate) struct HashCache {
+    variant: String,
+    entries: HashMap<String, StoredEntry>,
+    /// Inserts (Some) and removals (None) since the last load or save, replayed
+    /// onto the file's current content when saving
+    changes: HashMap<String, Option<StoredEntry>>,
+}
+
+impl HashCache {
+    /// Load the cache at `path`; a missing, corrupt or differently-configured file yields an empty cache
+    pub(crate) fn load(path: &Path, variant: &str) -> Self {
+        HashCache {
+            variant: variant.to_string(),
+            entries: read_entries(path, variant),
+            changes: HashMap::new(),
+        }
+    }
+
+    /// The cached hash for `path` if its size and mtime still match
+    pub(crate) fn get(&self, path: &str, size: u64, mtime: f64) -> Option<&str> {
+        self.entries
+            .get(path)
+            .map(|stored| &stored.entry)
+            .filter(|e| e.size == size && e.mtime == mtime)
+            .map(|e| e.hash.as_str())
+    }
+
+    pub(crate) fn insert(&mut self, path: String, entry: CacheEntry) {
+        let now = now_ms();
+        if now - entry.mtime < RACY_WINDOW_MS {
+            self.remove(&path);
+            return;
+        }
+        let stored = StoredEntry { entry, used_ms: now };
+        self.changes.insert(path.clone(), Some(stored.clone()));
+        self.entries.insert(path, stored);
+    }
+
+    pub(crate) fn remove(&mut self, path: &str) {
+        self.entries.remove(path);
+        self.changes.insert(path.to_string(), None);
+    }
+
+    /// Write the cache atomically (temp file + rename).
+    ///
+    /// Concurrent savers, e.g. addon instances in several workers, take turns
+    /// through a lock file next to `path`. Each replays its own changes onto the
+    /// file's current content, so entries stored by the others are kept. Entries
+    /// not stored for 30 days are dropped. Fails if the lock stays taken for 5 s.
+    pub(crate) fn save(&mut self, path: &Path) -> io::Result<()> {
+        let _lock = LockFile::acquire(path)?;
+        let mut entries = read_entries(path, &self.variant);
+        for (p, change) in self.changes.drain() {
+            match change {
+                Some(stored) => entries.insert(p, stored),
+                None => entries.remove(&p),
+            };
+        }
+        let cutoff = now_ms() - STALE_AFTER_MS;
+        entries.retain(|_, stored| stored.used_ms >= cutoff);
+        self.entries = entries;
+
+        let mut out = Vec::with_capacity(16 + self.entries.len() * 104);
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        out.extend_from_slice(&(self.variant.len() as u16).to_le_bytes());
+        out.extend_from_slice(self.variant.as_bytes());
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+
+        let mut paths: Vec<&String> = self.entries.keys().collect();
+        paths.sort();
+        for p in paths {
+            let StoredEntry { entry, used_ms } = &self.entries[p];
+            out.extend_from_slice(&(p.len() as u32).to_le_bytes());
+            out.extend_from_slice(p.as_bytes());
+            out.extend_from_slice(&entry.size.to_le_bytes());
+            out.extend_from_slice(&entry.mtime.to_le_bytes());
+            out.extend_from_slice(&used_ms.to_le_bytes());
+            out.push(entry.hash.len() as u8);
+            out.extend_from_slice(entry.hash.as_bytes());
+        }
+
+        write_atomic(path, &out)
+    }
+}
+
+/// Exclusive lock on a cache file, held while its lock file exists
+struct LockFile {
+    path: PathBuf,
+}
+
+impl LockFile {
+    fn acquire(cache_path: &Path) -> io::Result<Self> {
+        let mut path = OsString::from(cache_path.as_os_str());
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let started = SystemTime::now();
+        loop {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(LockFile { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .and_then(|m| m.modified())
+                        .is_ok_and(|t| t.elapsed().is_ok_and(|age| age > LOCK_STALE_AFTER));
+                    if stale {
+                        let _ = fs::remove_file(&path);
+                    } else if started.elapsed().is_ok_and(|waited| waited > LOCK_TIMEOUT) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::WouldBlock,
+                            format!("{} is held by another saver", path.display()),
+                        ));
+                    } else {
+                        thread::sleep(Duration::from_millis(5));
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn now_ms() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+/// Entries of the cache file at `path`; empty if it is missing, corrupt or
+/// written with another variant
+fn read_entries(path: &Path, variant: &str) -> HashMap<String, StoredEntry> {
+    fs::read(path)
+        .ok()
+        .and_then(|bytes| decode(&bytes, variant))
+        .unwrap_or_default()
+}
+
+/// Write through a temp file unique to this process and call, then rename over
+/// `path`, so readers (and maps of the old file) never see a partial write
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension(format!(
+        "tmp.{}.{}",
+        std::process::id(),
+        SAVE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+fn decode(bytes: &[u8], variant: &str) -> Option<HashMap<String, StoredEntry>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(4)? != MAGIC {
+        return None;
+    }
+    let version = reader.u8()?;
+    if version != FORMAT_VERSION_V1 && version != FORMAT_VERSION {
+        return None;
+    }
+    let variant_len = reader.u16()? as usize;
+    if reader.take(variant_len)? != variant.as_bytes() {
+        return None;
+    }
+
+    let loaded_ms = now_ms();
+    let count = reader.u32()? as usize;
+    let mut entries = HashMap::with_capacity(count);
+    for _ in 0..count {
+        let path_len = reader.u32()? as usize;
+        let path = std::str::from_utf8(reader.take(path_len)?).ok()?.to_string();
+        let size = reader.u64()?;
+        let mtime = f64::from_bits(reader.u64()?);
+        let used_ms = match version {
+            FORMAT_VERSION_V1 => loaded_ms,
+            _ => f64::from_bits(reader.u64()?),
+        };
+        let hash_len = reader.u8()? as usize;
+        let hash = std::str::from_utf8(reader.take(hash_len)?).ok()?.to_string();
+        let entry = CacheEntry { size, mtime, hash };
+        entries.insert(path, StoredEntry { entry, used_ms });
+    }
+    Some(entries)
+}
+
+/// Bounds-checked little-endian reader over a byte slice
+pub(crate) struct Reader<'a> {
+    pub bytes: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(slice)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    /// Unsigned LEB128 (see `push_varint`)
+    pub(crate) fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+/// Append `value` as unsigned LEB128, as used by multiformats
+pub(crate) fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash: &str) -> CacheEntry {
+        CacheEntry {
+            size: 1,
+            mtime: 1_600_000_000_000.0,
+            hash: hash.to_string(),
+        }
+    }
+
+    fn scratch_cache(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "codebase-native-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("hashes.cnhc")
+    }
+
+    #[test]
+    fn concurrent_saves_keep_each_others_entries() {
+        let path = scratch_cache("cache-merge");
+        let mut first = HashCache::load(&path, "v");
+        let mut second = HashCache::load(&path, "v");
+        first.insert("a".to_string(), entry("aa"));
+        second.insert("b".to_string(), entry("bb"));
+        first.save(&path).unwrap();
+        second.save(&path).unwrap();
+
+        let merged = HashCache::load(&path, "v");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(merged.get("a", 1, 1_600_000_000_000.0), Some("aa"));
+        assert_eq!(merged.get("b", 1, 1_600_000_000_000.0), Some("bb"));
+    }
+
+    #[test]
+    fn save_drops_stale_entries() {
+        let path = scratch_cache("cache-prune");
+        let mut cache = HashCache::load(&path, "v");
+        cache.insert("old".to_string(), entry("aa"));
+        cache.insert("new".to_string(), entry("bb"));
+        if let Some(Some(stored)) = cache.changes.get_mut("old") {
+            stored.used_ms -= STALE_AFTER_MS + 1.0;
+        }
+        cache.save(&path).unwrap();
+
+        let saved = HashCache::load(&path, "v");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(saved.get("old", 1, 1_600_000_000_000.0), None);
+        assert_eq!(saved.get("new", 1, 1_600_000_000_000.0), Some("bb"));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::hasher::{Digester, HashAlgorithm};
+use fastcdc::v2020::{self, StreamCDC};
+use napi_derive::napi;
+use std::fs;
+
+const DEFAULT_MIN_SIZE: u32 = 16 * 1024;
+const DEFAULT_AVG_SIZE: u32 = 64 * 1024;
+const DEFAULT_MAX_SIZE: u32 = 256 * 1024;
+
+/// Options for `chunk_file_cdc`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct CdcOptions {
+    /// Minimum chunk size in bytes (default 16 KiB, allowed 64 B – 1 MiB)
+    pub min: Option<u32>,
+    /// Target average chunk size in bytes (default 64 KiB, allowed 256 B – 4 MiB)
+    pub avg: Option<u32>,
+    /// Maximum chunk size in bytes (default 256 KiB, allowed 1 KiB – 16 MiB)
+    pub max: Option<u32>,
+    /// Digest algorithm for chunk hashes (default SHA-256)
+    pub algorithm: Option<HashAlgorithm>,
+}
+
+/// A content-defined chunk of a file
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct CdcChunk {
+    /// Byte offset of the chunk within the file
+    pub offset: i64,
+    /// Chunk length in bytes
+    pub length: u32,
+    /// Digest of the chunk bytes
+    pub hash: String,
+}
+
+/// Split a file into content-defined chunks with FastCDC (2020) and hash each chunk.
+/// Boundaries depend only on content, so an edit only changes the chunks around it.
+/// The file is streamed; memory use is bounded by `max`.
+#[napi(catch_unwind)]
+pub fn chunk_file_cdc(file_path: String, options: Option<CdcOptions>) -> napi::Result<Vec<CdcChunk>> {
+    let options = options.unwrap_or_default();
+    let min = options.min.unwrap_or(DEFAULT_MIN_SIZE);
+    let avg = options.avg.unwrap_or(DEFAULT_AVG_SIZE);
+    let max = options.max.unwrap_or(DEFAULT_MAX_SIZE);
+    validate_sizes(min, avg, max)?;
+    let algorithm = options.algorithm.unwrap_or_default();
+
+    let file = fs::File::open(&file_path).map_err(|e| {
+        napi::Error::from_reason(format!("Failed to read file {}: {}", file_path, e))
+    })?;
+
+    StreamCDC::new(file, min, avg, max)
+        .map(|chunk| {
+            let chunk = chunk.map_err(|e| {
+                napi::Error::from_reason(format!("Failed to chunk file {}: {}", file_path, e))
+            })?;
+            let mut digester = Digester::for_content(algorithm, chunk.length as u64);
+            digester.update(&chunk.data);
+            Ok(CdcChunk {
+                offset: chunk.offset as i64,
+                length: chunk.length as u32,
+                hash: digester.finalize_hex(),
+            })
+        })
+        .collect()
+}
+
+fn validate_sizes(min: u32, avg: u32, max: u32) -> napi::Result<()> {
+    let in_range = (v2020::MINIMUM_MIN..=v2020::MINIMUM_MAX).contains(&min)
+        && (v2020::AVERAGE_MIN..=v2020::AVERAGE_MAX).contains(&avg)
+        && (v2020::MAXIMUM_MIN..=v2020::MAXIMUM_MAX).contains(&max);
+    if !in_range || min > avg || avg > max {
+        return Err(napi::Error::from_reason(format!(
+            "Invalid CDC sizes min={} avg={} max={} (need min <= avg <= max within FastCDC limits)",
+            min, avg, max
+        )));
+    }
+    Ok(())
+}
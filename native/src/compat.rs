@@ -0,0 +1,236 @@
+use crate::cache::{CacheEntry, HashCache};
+use crate::fingerprint::{estimate_divergence, tree_fingerprint};
+use crate::hasher::HashAlgorithm;
+use crate::merkle::MerkleNode;
+use crate::treefile::{deserialize_tree, serialize_tree};
+use napi_derive::napi;
+use std::fs;
+use std::path::Path;
+
+/// Serialized trees, one per format version: file name, format version, hash
+/// scheme, namespace and whether files carry sizes
+const TREE_FIXTURES: [TreeFixture; 4] = [
+    ("tree.cnmt", 1, None, None, false),
+    ("tree-scheme.cnmt", 2, Some(2), None, false),
+    ("tree-namespace.cnmt", 3, Some(2), Some("workspace-ü"), false),
+    ("tree-sizes.cnmt", 4, Some(2), Some("workspace-ü"), true),
+];
+type TreeFixture = (&'static str, u8, Option<u32>, Option<&'static str>, bool);
+
+const CACHE_FIXTURE: &str = "hashes.cnhc";
+const CACHE_VARIANT: &str = "compat-fixture";
+const FINGERPRINT_FIXTURE: &str = "tree.cnfp";
+const FINGERPRINT_BITS: u32 = 256;
+
+/// Sizes above 4 GiB and fractional mtimes exercise every byte of the u64/f64 fields
+const CACHE_ENTRIES: [(&str, u64, f64, &str); 2] = [
+    ("src/a.ts", 0x0102_0304_0506_0708, 1_700_000_000_123.5, "00ff10ee"),
+    ("données/β.rs", 7, 1_600_000_000_000.25, "cafebabe"),
+];
+
+/// Result of checking one persisted format
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct FixtureCheck {
+    /// Fixture file name
+    pub format: String,
+    pub passed: bool,
+    pub error: Option<String>,
+}
+
+/// Write one fixture per persisted format (serialized tree in each format
+/// version, hash cache, tree fingerprint) into `dir`.
+/// Together with `verify_format_fixtures` on another machine this checks that
+/// files move between platforms (x86/ARM, glibc/musl) unchanged; every format
+/// uses explicit little-endian integers. Returns the written paths.
+#[napi(catch_unwind)]
+pub fn write_format_fixtures(dir: String) -> napi::Result<Vec<String>> {
+    let dir = Path::new(&dir);
+    fs::create_dir_all(dir).map_err(|e| {
+        napi::Error::from_reason(format!("Failed to create {}: {}", dir.display(), e))
+    })?;
+
+    let mut written = Vec::new();
+    for (name, _, scheme, namespace, sized) in TREE_FIXTURES {
+        let tree = serialize_tree(fixture_tree(scheme, namespace, sized))?;
+        written.push(write_fixture(&dir.join(name), &tree)?);
+    }
+
+    let cache_path = dir.join(CACHE_FIXTURE);
+    let _ = fs::remove_file(&cache_path);
+    let mut cache = HashCache::load(&cache_path, CACHE_VARIANT);
+    for (path, size, mtime, hash) in CACHE_ENTRIES {
+        let entry = CacheEntry {
+            size,
+            mtime,
+            hash: hash.to_string(),
+        };
+        cache.insert(path.to_string(), entry);
+    }
+    cache.save(&cache_path).map_err(|e| {
+        napi::Error::from_reason(format!("Failed to write {}: {}", cache_path.display(), e))
+    })?;
+    written.push(cache_path.to_string_lossy().into_owned());
+
+    let fingerprint = tree_fingerprint(fixture_tree(None, None, false), FINGERPRINT_BITS, None)?;
+    written.push(write_fixture(&dir.join(FINGERPRINT_FIXTURE), &fingerprint)?);
+    Ok(written)
+}
+
+fn write_fixture(path: &Path, bytes: &[u8]) -> napi::Result<String> {
+    fs::write(path, bytes).map_err(|e| {
+        napi::Error::from_reason(format!("Failed to write {}: {}", path.display(), e))
+    })?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Read fixtures written by `write_format_fixtures`, possibly on another
+/// platform, and check each decodes to the expected content
+#[napi(catch_unwind)]
+pub fn verify_format_fixtures(dir: String) -> Vec<FixtureCheck> {
+    let dir = Path::new(&dir);
+    let mut checks: Vec<FixtureCheck> = TREE_FIXTURES
+        .iter()
+        .map(|&(name, version, scheme, namespace, sized)| {
+            let result = verify_tree(&dir.join(name), version, scheme, namespace, sized);
+            check(name, result)
+        })
+        .collect();
+    checks.push(check(CACHE_FIXTURE, verify_cache(&dir.join(CACHE_FIXTURE))));
+    checks.push(check(
+        FINGERPRINT_FIXTURE,
+        verify_fingerprint(&dir.join(FINGERPRINT_FIXTURE)),
+    ));
+    checks
+}
+
+fn check(format: &str, result: Result<(), String>) -> FixtureCheck {
+    FixtureCheck {
+        format: format.to_string(),
+        passed: result.is_ok(),
+        error: result.err(),
+    }
+}
+
+fn verify_tree(
+    path: &Path,
+    version: u8,
+    scheme: Option<u32>,
+    namespace: Option<&str>,
+    sized: bool,
+) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    if bytes.get(4) != Some(&version) {
+        return Err(format!("expected format version {}", version));
+    }
+    let decoded = deserialize_tree(bytes.into()).map_err(|e| e.reason)?;
+    if summarize(decoded) != summarize(fixture_tree(scheme, namespace, sized)) {
+        return Err("decoded tree differs from the fixture".to_string());
+    }
+    Ok(())
+}
+
+fn verify_fingerprint(path: &Path) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let expected = tree_fingerprint(fixture_tree(None, None, false), FINGERPRINT_BITS, None)
+        .map_err(|e| e.reason)?;
+    if bytes != expected.as_ref() {
+        return Err("fingerprint differs from the fixture".to_string());
+    }
+    let estimate = estimate_divergence(bytes.into(), expected).map_err(|e| e.reason)?;
+    if estimate.files_a != 3 || estimate.different != 0.0 {
+        return Err("fingerprint did not decode to the fixture's files".to_string());
+    }
+    Ok(())
+}
+
+fn verify_cache(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Err(format!("{} not found", path.display()));
+    }
+    let cache = HashCache::load(path, CACHE_VARIANT);
+    for (entry_path, size, mtime, hash) in CACHE_ENTRIES {
+        if cache.get(entry_path, size, mtime) != Some(hash) {
+            return Err(format!("entry {} did not round-trip", entry_path));
+        }
+    }
+    Ok(())
+}
+
+/// Small tree with nested and non-ASCII paths and 32-byte hashes; with `sized`,
+/// a file above 4 GiB and an empty one exercise every byte of the size varints
+fn fixture_tree(
+    hash_scheme: Option<u32>,
+    namespace: Option<&str>,
+    sized: bool,
+) -> Vec<MerkleNode> {
+    let node = |path: &str, fill: u8, children: &[&str], size: i64, file_count: u32| {
+        MerkleNode {
+            path: path.to_string(),
+            hash: hex::encode([fill; 32]),
+            is_file: children.is_empty(),
+            children: children.iter().map(|c| c.to_string()).collect(),
+            algorithm: Some(HashAlgorithm::Blake3),
+            is_root: (path == ".").then_some(true),
+            size: sized.then_some(size),
+            file_count: Some(file_count),
+            hash_scheme,
+            namespace: namespace.map(str::to_string),
+        }
+    };
+    vec![
+        node(".", 0x01, &["README.md", "src"], 0x0102_0304_0506_0710, 3),
+        node("README.md", 0x02, &[], 0x0102_0304_0506_0708, 1),
+        node("src", 0x03, &["src/lib.rs", "src/é"], 8, 2),
+        node("src/lib.rs", 0x04, &[], 8, 1),
+        node("src/é", 0x05, &["src/é/β.rs"], 0, 1),
+        node("src/é/β.rs", 0xfe, &[], 0, 1),
+    ]
+}
+
+type NodeSummary = (
+    String,
+    String,
+    bool,
+    Vec<String>,
+    Option<i64>,
+    Option<u32>,
+    u32,
+    Option<String>,
+);
+
+fn summarize(nodes: Vec<MerkleNode>) -> Vec<NodeSummary> {
+    let mut summary: Vec<_> = nodes
+        .into_iter()
+        .map(|n| {
+            let mut children = n.children;
+            children.sort();
+            let scheme = n.hash_scheme.unwrap_or(1);
+            let (size, file_count) = (n.size, n.file_count);
+            (n.path, n.hash, n.is_file, children, size, file_count, scheme, n.namespace)
+        })
+        .collect();
+    summary.sort();
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures_round_trip() {
+        let dir = std::env::temp_dir()
+            .join(format!("codebase-native-test-fx-{}", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let written = write_format_fixtures(dir.clone()).unwrap();
+        let checks = verify_format_fixtures(dir.clone());
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(written.len(), checks.len());
+        for check in checks {
+            assert!(check.passed, "{}: {:?}", check.format, check.error);
+        }
+    }
+}
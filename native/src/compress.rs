@@ -0,0 +1,83 @@
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use std::io::Read;
+
+/// zstd's own default compression level
+const DEFAULT_LEVEL: i32 = 3;
+/// Default dictionary size, as used by `zstd --train`
+const DEFAULT_DICTIONARY_SIZE: u32 = 110 * 1024;
+/// Default cap on decompressed output, so a small frame cannot expand without bound
+const DEFAULT_MAX_OUTPUT_SIZE: i64 = 256 * 1024 * 1024;
+
+/// Compress bytes with zstd (default level 3), optionally with a dictionary from
+/// `train_zstd_dictionary`; the same dictionary is then required to decompress
+#[napi(catch_unwind)]
+pub fn compress_zstd(
+    data: Buffer,
+    level: Option<i32>,
+    dictionary: Option<Buffer>,
+) -> napi::Result<Buffer> {
+    let level = level.unwrap_or(DEFAULT_LEVEL);
+    if !zstd::compression_level_range().contains(&level) {
+        return Err(napi::Error::from_reason(format!(
+            "Invalid zstd level {} (expected {:?})",
+            level,
+            zstd::compression_level_range()
+        )));
+    }
+    let dictionary = dictionary.as_deref().unwrap_or_default();
+    zstd::bulk::Compressor::with_dictionary(level, dictionary)
+        .and_then(|mut compressor| compressor.compress(&data))
+        .map(Buffer::from)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to compress: {}", e)))
+}
+
+/// Decompress a zstd frame produced by `compress_zstd` (with the same dictionary, if any).
+/// Fails if the output would exceed `max_output_size` bytes (default 256 MiB).
+#[napi(catch_unwind)]
+pub fn decompress_zstd(
+    data: Buffer,
+    dictionary: Option<Buffer>,
+    max_output_size: Option<i64>,
+) -> napi::Result<Buffer> {
+    let limit = max_output_size.unwrap_or(DEFAULT_MAX_OUTPUT_SIZE).max(0) as u64;
+    let dictionary = dictionary.as_deref().unwrap_or_default();
+    let mut out = Vec::new();
+    // Read one byte past the limit to tell "exactly at the limit" from "over it"
+    zstd::stream::read::Decoder::with_dictionary(&data[..], dictionary)
+        .and_then(|decoder| decoder.take(limit + 1).read_to_end(&mut out))
+        .map_err(|e| napi::Error::from_reason(format!("Failed to decompress: {}", e)))?;
+    if out.len() as u64 > limit {
+        return Err(napi::Error::from_reason(format!(
+            "Decompressed data exceeds {} bytes",
+            limit
+        )));
+    }
+    Ok(out.into())
+}
+
+/// Train a zstd dictionary (default at most 110 KiB) from sample payloads such as
+/// code chunks. Needs a reasonable number of samples; zstd rejects too few.
+#[napi(catch_unwind)]
+pub fn train_zstd_dictionary(samples: Vec<Buffer>, max_size: Option<u32>) -> napi::Result<Buffer> {
+    let samples: Vec<&[u8]> = samples.iter().map(|s| s.as_ref()).collect();
+    let max_size = max_size.unwrap_or(DEFAULT_DICTIONARY_SIZE) as usize;
+    zstd::dict::from_samples(&samples, max_size)
+        .map(Buffer::from)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to train dictionary: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompression_stops_at_max_output_size() {
+        let data = compress_zstd(vec![0u8; 4096].into(), None, None).unwrap();
+
+        let exact = decompress_zstd(data.to_vec().into(), None, Some(4096)).unwrap();
+        assert_eq!(exact.len(), 4096);
+        assert!(decompress_zstd(data.to_vec().into(), None, Some(4095)).is_err());
+        assert!(decompress_zstd(data, None, None).is_ok());
+    }
+}
@@ -0,0 +1,189 @@
+use napi_derive::napi;
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static INSTALL_HOOK: Once = Once::new();
+static CRASH_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+static LAST_CRASH: Mutex<Option<CrashReport>> = Mutex::new(None);
+static REDACTED_PATHS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static RECENT_EVENTS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Trace events kept for crash reports; older ones are dropped
+const MAX_RECENT_EVENTS: usize = 64;
+
+/// Options for `install_crash_handler`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct CrashHandlerOptions {
+    /// Paths replaced with `<redacted>` in crash files, reports and trace
+    /// events, e.g. the workspace roots. The home directory is always
+    /// replaced with `~`.
+    pub redact_paths: Option<Vec<String>>,
+}
+
+/// Details of a panic in native code
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct CrashReport {
+    /// Panic message
+    pub message: String,
+    /// `file:line:column` of the panic, redacted like the message
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    /// Crash file holding the redacted backtrace, if it could be written
+    pub crash_file: Option<String>,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: f64,
+    /// Most recent trace events before the crash, oldest first
+    pub recent_events: Vec<String>,
+}
+
+/// Install a panic hook that writes a redacted native backtrace to a file in
+/// `crash_dir` and records the crash for `take_last_crash`. Panics in the I/O
+/// entry points are turned into JS errors instead of aborting the host.
+/// Calling again only changes the directory and redacted paths. The hook is
+/// process-wide and shared by instances loaded in worker threads.
+#[napi(catch_unwind)]
+pub fn install_crash_handler(
+    crash_dir: String,
+    options: Option<CrashHandlerOptions>,
+) -> napi::Result<()> {
+    fs::create_dir_all(&crash_dir).map_err(|e| {
+        napi::Error::from_reason(format!("Failed to create crash directory {}: {}", crash_dir, e))
+    })?;
+    *lock(&CRASH_DIR) = Some(PathBuf::from(crash_dir));
+    let mut redacted = options.unwrap_or_default().redact_paths.unwrap_or_default();
+    redacted.retain(|path| path.len() > 1);
+    // Longest first, so a nested root is not left half-replaced by its parent
+    redacted.sort_by_key(|path| std::cmp::Reverse(path.len()));
+    *lock(&REDACTED_PATHS) = redacted;
+    INSTALL_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            record_crash(info);
+            previous(info);
+        }));
+    });
+    Ok(())
+}
+
+/// Add an event to the trace kept for crash reports, e.g. the operation the
+/// extension is about to run. Only the last 64 events are kept.
+#[napi(catch_unwind)]
+pub fn record_trace_event(event: String) {
+    trace_event(event);
+}
+
+/// Record a native trace event for crash reports
+pub(crate) fn trace_event(event: String) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let mut events = lock(&RECENT_EVENTS);
+    if events.len() == MAX_RECENT_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(format!("{} {}", timestamp, event));
+}
+
+/// The most recent native crash since the last call, if any
+#[napi(catch_unwind)]
+pub fn take_last_crash() -> Option<CrashReport> {
+    lock(&LAST_CRASH).take()
+}
+
+fn record_crash(info: &PanicHookInfo) {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    let location = info
+        .location()
+        .map(|l| redact(&format!("{}:{}:{}", l.file(), l.line(), l.column())));
+    let thread = thread::current().name().map(str::to_string);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0);
+
+    let recent_events: Vec<String> = lock(&RECENT_EVENTS).iter().map(|e| redact(e)).collect();
+
+    let report = format!(
+        "native crash\nmessage: {}\nlocation: {}\nthread: {}\n\n{}\nrecent events:\n{}\n",
+        message,
+        location.as_deref().unwrap_or("unknown"),
+        thread.as_deref().unwrap_or("unnamed"),
+        Backtrace::force_capture(),
+        recent_events.join("\n")
+    );
+    let crash_file = lock(&CRASH_DIR).clone().and_then(|dir| {
+        let path = dir.join(format!(
+            "native-crash-{}-{}.txt",
+            timestamp as u64,
+            std::process::id()
+        ));
+        fs::write(&path, redact(&report)).ok()?;
+        Some(path.to_string_lossy().into_owned())
+    });
+
+    *lock(&LAST_CRASH) = Some(CrashReport {
+        message: redact(&message),
+        location,
+        thread,
+        crash_file,
+        timestamp,
+        recent_events,
+    });
+}
+
+/// Replace the configured paths with `<redacted>` and the user's home directory
+/// with `~` so crash files can be shared
+fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    for path in lock(&REDACTED_PATHS).iter() {
+        text = text.replace(path.as_str(), "<redacted>");
+    }
+    match std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")) {
+        Ok(home) if home.len() > 1 => text.replace(&home, "~"),
+        _ => text,
+    }
+}
+
+/// Lock that stays usable after a panic poisoned it (we are often called mid-panic)
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_keeps_only_recent_events() {
+        for i in 0..MAX_RECENT_EVENTS + 10 {
+            trace_event(format!("ring test {}", i));
+        }
+        let events = lock(&RECENT_EVENTS);
+        assert_eq!(events.len(), MAX_RECENT_EVENTS);
+        assert!(!events.iter().any(|e| e.ends_with(" ring test 0")));
+        let newest = format!(" ring test {}", MAX_RECENT_EVENTS + 9);
+        assert!(events.iter().any(|e| e.ends_with(&newest)));
+    }
+
+    #[test]
+    fn redact_replaces_configured_paths() {
+        *lock(&REDACTED_PATHS) = vec!["/work/app/sub".to_string(), "/work/app".to_string()];
+        let text = redact("at /work/app/src/a.rs and /work/app/sub/b.rs");
+        *lock(&REDACTED_PATHS) = vec![];
+        assert_eq!(text, "at <redacted>/src/a.rs and <redacted>/b.rs");
+    }
+}
@@ -0,0 +1,184 @@
+use crate::cache::Reader;
+use crate::merkle::MerkleNode;
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+
+/// File magic and format version of tree fingerprints
+const MAGIC: &[u8; 4] = b"CNFP";
+const FORMAT_VERSION: u8 = 1;
+
+const MIN_BITS: u32 = 64;
+const MAX_BITS: u32 = 1 << 30;
+const DEFAULT_HASH_COUNT: u32 = 3;
+const MAX_HASH_COUNT: u32 = 16;
+
+/// Union fill ratio above which estimates are reported as unreliable
+const SATURATION: f64 = 0.95;
+
+/// Options for `tree_fingerprint`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct FingerprintOptions {
+    /// Bits set per file (default 3); fingerprints are only comparable with
+    /// the same size and hash count
+    pub hash_count: Option<u32>,
+}
+
+/// Result of `estimate_divergence`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct DivergenceEstimate {
+    /// Exact file counts recorded in the fingerprints
+    pub files_a: u32,
+    pub files_b: u32,
+    /// Estimated files present with the same hash on both sides
+    pub shared: f64,
+    /// Estimated files present on one side only; a modified file counts once per side
+    pub different: f64,
+    /// Estimated Jaccard similarity of the two file sets, 1 for two empty trees
+    pub similarity: f64,
+    /// Whether the filters are too full for a useful estimate; use more bits
+    pub saturated: bool,
+}
+
+/// Constant-size Bloom filter over a tree's (path, hash) file entries, for
+/// estimating how far two trees diverge before running a full diff. `bits` is
+/// rounded up to a whole number of bytes (at least 64); about 10 bits per file
+/// keeps estimates within a few percent. Hashing is unkeyed, so any peer can
+/// compute a comparable fingerprint. Layout, integers little-endian:
+///
+/// ```text
+/// "CNFP" u8:version u8:hash_count u32:bits u32:file_count bits/8 bytes
+/// ```
+#[napi(catch_unwind)]
+pub fn tree_fingerprint(
+    nodes: Vec<MerkleNode>,
+    bits: u32,
+    options: Option<FingerprintOptions>,
+) -> napi::Result<Buffer> {
+    let hash_count = options
+        .unwrap_or_default()
+        .hash_count
+        .unwrap_or(DEFAULT_HASH_COUNT);
+    if hash_count == 0 || hash_count > MAX_HASH_COUNT {
+        return Err(napi::Error::from_reason(format!(
+            "hashCount must be between 1 and {}",
+            MAX_HASH_COUNT
+        )));
+    }
+    if bits > MAX_BITS {
+        return Err(napi::Error::from_reason(format!(
+            "bits must be at most {}",
+            MAX_BITS
+        )));
+    }
+    let bits = bits.max(MIN_BITS).next_multiple_of(8);
+
+    let mut filter = vec![0u8; bits as usize / 8];
+    let mut file_count = 0u32;
+    for node in nodes.iter().filter(|n| n.is_file) {
+        file_count += 1;
+        for bit in bit_positions(&node.path, &node.hash, bits, hash_count) {
+            filter[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    let mut out = Vec::with_capacity(14 + filter.len());
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_VERSION);
+    out.push(hash_count as u8);
+    out.extend_from_slice(&bits.to_le_bytes());
+    out.extend_from_slice(&file_count.to_le_bytes());
+    out.extend_from_slice(&filter);
+    Ok(out.into())
+}
+
+/// Estimate how many files two trees share from their `tree_fingerprint`s,
+/// using the fill ratios of each filter and of their union
+#[napi(catch_unwind)]
+pub fn estimate_divergence(a: Buffer, b: Buffer) -> napi::Result<DivergenceEstimate> {
+    let a = Fingerprint::parse(&a)
+        .ok_or_else(|| napi::Error::from_reason("Invalid or corrupt fingerprint a"))?;
+    let b = Fingerprint::parse(&b)
+        .ok_or_else(|| napi::Error::from_reason("Invalid or corrupt fingerprint b"))?;
+    if a.bits != b.bits || a.hash_count != b.hash_count {
+        return Err(napi::Error::from_reason(format!(
+            "Fingerprints are not comparable ({} bits x {} hashes vs {} bits x {} hashes)",
+            a.bits, a.hash_count, b.bits, b.hash_count
+        )));
+    }
+
+    let set_a = count_ones(a.filter.iter().copied());
+    let set_b = count_ones(b.filter.iter().copied());
+    let set_union = count_ones(a.filter.iter().zip(b.filter).map(|(x, y)| x | y));
+    let saturated = set_union as f64 >= SATURATION * a.bits as f64;
+
+    let estimate = |set: u32| -> f64 {
+        // Swamidass & Baldi: n = -(m / k) ln(1 - X / m), capped below a full filter
+        let m = a.bits as f64;
+        let fill = (set as f64).min(m - 1.0) / m;
+        -(m / a.hash_count as f64) * (1.0 - fill).ln()
+    };
+    let (n_a, n_b, n_union) = (estimate(set_a), estimate(set_b), estimate(set_union));
+    let shared = (n_a + n_b - n_union).clamp(0.0, a.file_count.min(b.file_count) as f64);
+    let different = (a.file_count as f64 + b.file_count as f64 - 2.0 * shared).max(0.0);
+    let total = shared + different;
+    Ok(DivergenceEstimate {
+        files_a: a.file_count,
+        files_b: b.file_count,
+        shared,
+        different,
+        similarity: if total == 0.0 { 1.0 } else { shared / total },
+        saturated,
+    })
+}
+
+struct Fingerprint<'a> {
+    hash_count: u32,
+    bits: u32,
+    file_count: u32,
+    filter: &'a [u8],
+}
+
+impl<'a> Fingerprint<'a> {
+    fn parse(bytes: &'a [u8]) -> Option<Self> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != MAGIC || reader.u8()? != FORMAT_VERSION {
+            return None;
+        }
+        let hash_count = reader.u8()? as u32;
+        let bits = reader.u32()?;
+        let file_count = reader.u32()?;
+        let filter = reader.take(bits as usize / 8)?;
+        let valid = (1..=MAX_HASH_COUNT).contains(&hash_count)
+            && bits >= MIN_BITS
+            && bits.is_multiple_of(8)
+            && reader.pos == bytes.len();
+        valid.then_some(Fingerprint {
+            hash_count,
+            bits,
+            file_count,
+            filter,
+        })
+    }
+}
+
+fn count_ones(bytes: impl Iterator<Item = u8>) -> u32 {
+    bytes.map(u8::count_ones).sum()
+}
+
+/// Filter bits of one file entry, by double hashing a BLAKE3 digest of
+/// `path NUL hash`
+fn bit_positions(path: &str, hash: &str, bits: u32, hash_count: u32) -> Vec<usize> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(path.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(hash.as_bytes());
+    let digest = hasher.finalize();
+    let digest = digest.as_bytes();
+    let h1 = u64::from_le_bytes(digest[..8].try_into().unwrap());
+    let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap()) | 1;
+    (0..hash_count as u64)
+        .map(|i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits as u64) as usize)
+        .collect()
+}
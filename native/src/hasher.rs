@@ -1,44 +1,572 @@
+use crate::cache::{push_varint, CacheEntry, HashCache};
+use crate::crash::trace_event;
+use crate::merkle::FileHashEntry;
+use crate::scanner::mtime_millis;
+use crate::throttle::Throttle;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use memmap2::Mmap;
+use napi::bindgen_prelude::Buffer;
 use napi_derive::napi;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use sha1::Sha1;
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Read buffer size used when streaming file contents into a hasher
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Thread pools of `hash_files` by `max_concurrency`, built on first use and kept
+/// for the life of the process
+static HASH_POOLS: Mutex<BTreeMap<u32, Arc<ThreadPool>>> = Mutex::new(BTreeMap::new());
+
+/// How often a file that changes while being hashed is re-read before giving up
+const HASH_ATTEMPTS: usize = 3;
+
+/// Digest algorithm for file and tree hashes
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// SHA-256 (default, compatible with previously stored hashes)
+    #[default]
+    Sha256,
+    /// BLAKE3, several times faster than SHA-256 and internally parallel on large inputs
+    Blake3,
+    /// 64-bit XXH3; non-cryptographic, for local change detection only
+    Xxh3,
+    /// Git blob id: SHA-1 over `blob <len>\0` + content, as `git hash-object` prints.
+    /// Directory hashes use plain SHA-1 over the child hashes.
+    GitSha1,
+    /// Git blob id for SHA-256 repositories (`git hash-object --object-format=sha256`).
+    /// Directory hashes use plain SHA-256 over the child hashes.
+    GitSha256,
+}
+
+/// How file digests are rendered
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum DigestEncoding {
+    /// Lowercase hex (default)
+    #[default]
+    Hex,
+    /// URL-safe base64 without padding (RFC 4648 section 5)
+    Base64Url,
+    /// Raw bytes in `FileHash.digest`, leaving `hash` empty. Only batch results
+    /// carry buffers; APIs returning a single string fall back to hex.
+    Buffer,
+}
+
+impl HashAlgorithm {
+    /// Multicodec code of the hash function, for multihash output
+    fn multihash_code(self) -> u64 {
+        match self {
+            HashAlgorithm::Sha256 | HashAlgorithm::GitSha256 => 0x12,
+            HashAlgorithm::Blake3 => 0x1e,
+            HashAlgorithm::Xxh3 => 0xb3e3,
+            HashAlgorithm::GitSha1 => 0x11,
+        }
+    }
+}
+
+/// How symbolic links are hashed
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum SymlinkMode {
+    /// Hash the contents of the link target (default); broken links cannot be hashed
+    #[default]
+    Follow,
+    /// Hash the link target path string itself, never reading through the link
+    HashTarget,
+}
+
+impl HashAlgorithm {
+    /// Whether content digests are framed with a git object header
+    pub(crate) fn is_git(self) -> bool {
+        matches!(self, HashAlgorithm::GitSha1 | HashAlgorithm::GitSha256)
+    }
+}
+
+/// Options shared by the file hashing APIs
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct HashOptions {
+    /// Digest algorithm (default SHA-256); the `sha256*` functions reject any other
+    pub algorithm: Option<HashAlgorithm>,
+    /// Hash files of at least 64 KiB through a read-only memory map instead of
+    /// buffered reads; falls back to buffered reads if mapping fails
+    pub use_mmap: Option<bool>,
+    /// How symbolic links are hashed (default: follow)
+    pub symlinks: Option<SymlinkMode>,
+    /// Also return size and mtime, taken from the same open handle as the hashed content
+    pub include_stat: Option<bool>,
+    /// Persistent hash cache file used by `hash_files`: files whose size and mtime match
+    /// their cached entry are not re-read. Created or rewritten after each batch.
+    pub cache_path: Option<String>,
+    /// Hash text files as if CRLF line endings were LF, so checkouts on different
+    /// platforms agree; binary files (NUL in the first 8000 bytes) are hashed as-is
+    pub normalize_line_endings: Option<bool>,
+    /// Quick-hash mode for huge files: when a file is larger than twice this many bytes,
+    /// hash only its length plus this many bytes from the start and from the end.
+    /// Such hashes are a heuristic change signal and are flagged with `sampled`.
+    /// 0 disables sampling.
+    pub sample_size: Option<u32>,
+    /// Ask the kernel to start reading each file ahead of the hasher (Linux
+    /// `posix_fadvise`); a no-op elsewhere or when the hint is rejected
+    pub read_ahead: Option<bool>,
+    /// Maximum number of files `hash_files` reads at once (default: one per CPU)
+    pub max_concurrency: Option<u32>,
+    /// Read budget for `hash_files` in bytes per second, shared by all workers;
+    /// files are charged by size before they are read. Cache hits are free.
+    pub max_bytes_per_second: Option<i64>,
+    /// Emit file hashes as self-describing multihashes (varint code + varint
+    /// length + digest) with a multibase prefix: `f` for hex, `u` for base64url.
+    /// Rejected while a hash key is installed: multicodec has no codes for keyed
+    /// digests, and the plain function's code would misdescribe them.
+    pub multihash: Option<bool>,
+    /// How file hashes are rendered (default lowercase hex)
+    pub encoding: Option<DigestEncoding>,
+}
+
+impl HashOptions {
+    /// Whether a file of `len` bytes is hashed in sampled mode
+    fn samples(&self, len: u64) -> bool {
+        self.sample_bytes().is_some_and(|n| len > 2 * n as u64)
+    }
+
+    /// Bytes hashed from each end in sampled mode, None when sampling is off
+    fn sample_bytes(&self) -> Option<u32> {
+        self.sample_size.filter(|&n| n > 0)
+    }
+
+    /// Render a file digest as configured. `Buffer` encoding is rendered as hex here
+    /// and converted by `hash_files` once the batch (and its cache) is done.
+    fn format_digest(&self, digest: Vec<u8>) -> String {
+        let multihash = self.multihash == Some(true);
+        let bytes = if multihash {
+            let mut bytes = Vec::with_capacity(digest.len() + 4);
+            push_varint(&mut bytes, self.algorithm.unwrap_or_default().multihash_code());
+            push_varint(&mut bytes, digest.len() as u64);
+            bytes.extend_from_slice(&digest);
+            bytes
+        } else {
+            digest
+        };
+        match (self.encoding.unwrap_or_default(), multihash) {
+            (DigestEncoding::Base64Url, false) => URL_SAFE_NO_PAD.encode(bytes),
+            (DigestEncoding::Base64Url, true) => format!("u{}", URL_SAFE_NO_PAD.encode(bytes)),
+            (_, false) => hex::encode(bytes),
+            (_, true) => format!("f{}", hex::encode(bytes)),
+        }
+    }
+
+    /// Raw bytes behind a hash rendered by `format_digest` with hex encoding
+    fn digest_bytes(&self, hash: &str) -> Vec<u8> {
+        let hex_digits = match self.multihash {
+            Some(true) => hash.strip_prefix('f').unwrap_or(hash),
+            _ => hash,
+        };
+        hex::decode(hex_digits).unwrap_or_default()
+    }
+
+    /// Identifies every setting that changes a digest, so cached hashes are only
+    /// reused under identical settings
+    fn cache_variant(&self) -> String {
+        format!(
+            "{:?};crlf={};sample={:?};mh={};enc={:?};key={}",
+            self.algorithm.unwrap_or_default(),
+            self.normalize_line_endings == Some(true),
+            self.sample_bytes(),
+            self.multihash == Some(true),
+            self.encoding.unwrap_or_default(),
+            current_hash_key().map_or_else(String::new, |k| k.id.clone())
+        )
+    }
+}
 
 /// Compute SHA-256 hash of a string
-#[napi]
+#[napi(catch_unwind)]
 pub fn sha256_hash(content: String) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
-    hex::encode(hasher.finalize())
+    hash_string(content, Some(HashAlgorithm::Sha256))
+}
+
+/// Compute SHA-256 hash of raw bytes (no UTF-8 conversion)
+#[napi(catch_unwind)]
+pub fn sha256_hash_buffer(content: Buffer) -> String {
+    hash_buffer(content, Some(HashAlgorithm::Sha256))
+}
+
+/// Batch compute SHA-256 hashes of raw byte buffers (parallel via rayon), in input order
+#[napi(catch_unwind)]
+pub fn sha256_hash_buffers(contents: Vec<Buffer>) -> Vec<String> {
+    use rayon::prelude::*;
+
+    let slices: Vec<&[u8]> = contents.iter().map(|b| b.as_ref()).collect();
+    slices
+        .par_iter()
+        .map(|bytes| hash_bytes(bytes, HashAlgorithm::Sha256))
+        .collect()
 }
 
 /// Compute SHA-256 hash of a file's contents
+#[napi(catch_unwind)]
+pub fn sha256_hash_file(file_path: String, options: Option<HashOptions>) -> napi::Result<String> {
+    hash_file(file_path, Some(with_sha256(options)?))
+}
+
+/// Batch compute SHA-256 hashes for multiple files (parallel via rayon).
+/// Files that cannot be hashed are listed in `failed` with the reason.
+#[napi(catch_unwind)]
+pub fn sha256_hash_files(
+    file_paths: Vec<String>,
+    options: Option<HashOptions>,
+) -> napi::Result<HashFilesResult> {
+    hash_files(file_paths, Some(with_sha256(options)?))
+}
+
+/// Hash a string with the given algorithm (default SHA-256)
+#[napi(catch_unwind)]
+pub fn hash_string(content: String, algorithm: Option<HashAlgorithm>) -> String {
+    hash_bytes(content.as_bytes(), algorithm.unwrap_or_default())
+}
+
+/// Hash raw bytes with the given algorithm (default SHA-256)
+#[napi(catch_unwind)]
+pub fn hash_buffer(content: Buffer, algorithm: Option<HashAlgorithm>) -> String {
+    hash_bytes(&content, algorithm.unwrap_or_default())
+}
+
+fn hash_bytes(content: &[u8], algorithm: HashAlgorithm) -> String {
+    let mut digester = Digester::for_content(algorithm, content.len() as u64);
+    digester.update(content);
+    digester.finalize_hex()
+}
+
+/// Incremental hasher for content that arrives in pieces (e.g. from a stream).
+/// Produces the same digest as `hash_buffer` over the concatenated input.
 #[napi]
-pub fn sha256_hash_file(file_path: String) -> napi::Result<String> {
-    let content = fs::read(&file_path).map_err(|e| {
-        napi::Error::from_reason(format!("Failed to read file {}: {}", file_path, e))
-    })?;
-    let mut hasher = Sha256::new();
-    hasher.update(&content);
-    Ok(hex::encode(hasher.finalize()))
+pub struct Hasher {
+    digester: Option<Digester>,
+    /// Declared total length; required by the git variants, whose header includes it
+    expected_len: Option<u64>,
+    written: u64,
 }
 
-/// Batch compute SHA-256 hashes for multiple files (parallel via rayon)
 #[napi]
-pub fn sha256_hash_files(file_paths: Vec<String>) -> Vec<FileHash> {
+impl Hasher {
+    #[napi(constructor, catch_unwind)]
+    pub fn new(algorithm: Option<HashAlgorithm>, content_length: Option<i64>) -> napi::Result<Self> {
+        let algorithm = algorithm.unwrap_or_default();
+        let expected_len = match content_length {
+            Some(len) if len < 0 => {
+                return Err(napi::Error::from_reason(format!(
+                    "Invalid content length {}",
+                    len
+                )))
+            }
+            Some(len) => Some(len as u64),
+            None if algorithm.is_git() => {
+                return Err(napi::Error::from_reason(format!(
+                    "{:?} hashing requires the content length up front",
+                    algorithm
+                )))
+            }
+            None => None,
+        };
+        Ok(Hasher {
+            digester: Some(Digester::for_content(algorithm, expected_len.unwrap_or(0))),
+            expected_len,
+            written: 0,
+        })
+    }
+
+    /// Feed the next piece of content
+    #[napi(catch_unwind)]
+    pub fn update(&mut self, data: Buffer) -> napi::Result<()> {
+        let digester = self
+            .digester
+            .as_mut()
+            .ok_or_else(|| napi::Error::from_reason("Hasher already finalized".to_string()))?;
+        digester.update(&data);
+        self.written += data.len() as u64;
+        Ok(())
+    }
+
+    /// Finish and return the hex digest; the hasher cannot be used afterwards
+    #[napi(catch_unwind)]
+    pub fn digest_hex(&mut self) -> napi::Result<String> {
+        let digester = self
+            .digester
+            .take()
+            .ok_or_else(|| napi::Error::from_reason("Hasher already finalized".to_string()))?;
+        if let Some(expected) = self.expected_len.filter(|&len| len != self.written) {
+            return Err(napi::Error::from_reason(format!(
+                "Content length mismatch: declared {} bytes, received {}",
+                expected, self.written
+            )));
+        }
+        Ok(digester.finalize_hex())
+    }
+}
+
+/// Hash a file's contents with the algorithm selected in `options`
+#[napi(catch_unwind)]
+pub fn hash_file(file_path: String, options: Option<HashOptions>) -> napi::Result<String> {
+    hash_entry(Path::new(&file_path), &checked_options(options)?)
+        .map(|entry| entry.hash)
+        .map_err(|e| {
+            napi::Error::from_reason(format!("Failed to read file {}: {}", file_path, e))
+        })
+}
+
+/// Batch hash multiple files with the algorithm selected in `options` (parallel via rayon).
+/// Files that cannot be hashed are listed in `failed` with the reason.
+#[napi(catch_unwind)]
+pub fn hash_files(
+    file_paths: Vec<String>,
+    options: Option<HashOptions>,
+) -> napi::Result<HashFilesResult> {
     use rayon::prelude::*;
 
-    file_paths
-        .par_iter()
-        .filter_map(|path| {
-            let content = fs::read(path).ok()?;
-            let mut hasher = Sha256::new();
-            hasher.update(&content);
-            Some(FileHash {
-                path: path.clone(),
-                hash: hex::encode(hasher.finalize()),
+    trace_event(format!("hash {} files", file_paths.len()));
+    let options = checked_options(options)?;
+    let cache = options
+        .cache_path
+        .as_ref()
+        .map(|p| HashCache::load(Path::new(p), &options.cache_variant()));
+    let throttle = options.max_bytes_per_second.and_then(Throttle::new);
+    let hash_all = || -> Vec<Result<FileHash, HashFailure>> {
+        file_paths
+            .par_iter()
+            .map(|path| {
+                let hashed = match &cache {
+                    Some(cache) => {
+                        hash_entry_cached(Path::new(path), &options, cache, throttle.as_ref())
+                    }
+                    None => {
+                        if let Some(throttle) = &throttle {
+                            throttle.acquire(fs::metadata(path).map(|m| m.len()).unwrap_or(0));
+                        }
+                        hash_entry(Path::new(path), &options)
+                    }
+                };
+                hashed.map_err(|e| HashFailure::new(path, &e))
             })
-        })
-        .collect()
+            .collect()
+    };
+    // A dedicated pool caps concurrent reads; if it cannot be built, use the global one
+    let results = match options.max_concurrency.filter(|&n| n > 0).and_then(hash_pool) {
+        Some(pool) => pool.install(hash_all),
+        None => hash_all(),
+    };
+
+    let mut batch = HashFilesResult {
+        hashes: Vec::with_capacity(results.len()),
+        failed: Vec::new(),
+    };
+    for result in results {
+        match result {
+            Ok(hash) => batch.hashes.push(hash),
+            Err(failure) => batch.failed.push(failure),
+        }
+    }
+
+    if let (Some(mut cache), Some(cache_path)) = (cache, &options.cache_path) {
+        for hash in &batch.hashes {
+            if let (None, Some(size), Some(mtime)) = (&hash.symlink_target, hash.size, hash.mtime) {
+                let entry = CacheEntry {
+                    size: size as u64,
+                    mtime,
+                    hash: hash.hash.clone(),
+                };
+                cache.insert(hash.path.clone(), entry);
+            }
+        }
+        for failure in &batch.failed {
+            cache.remove(&failure.path);
+        }
+        // The cache is an optimization only; failing to persist it must not fail hashing
+        let _ = cache.save(Path::new(cache_path));
+        if options.include_stat != Some(true) {
+            for hash in &mut batch.hashes {
+                hash.size = None;
+                hash.mtime = None;
+            }
+        }
+    }
+    if options.encoding == Some(DigestEncoding::Buffer) {
+        for hash in &mut batch.hashes {
+            let hex_hash = std::mem::take(&mut hash.hash);
+            hash.digest = Some(options.digest_bytes(&hex_hash).into());
+        }
+    }
+    Ok(batch)
+}
+
+/// The shared pool with `threads` workers, or None if it cannot be built
+fn hash_pool(threads: u32) -> Option<Arc<ThreadPool>> {
+    let mut pools = HASH_POOLS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = pools.get(&threads) {
+        return Some(Arc::clone(pool));
+    }
+    let pool = Arc::new(ThreadPoolBuilder::new().num_threads(threads as usize).build().ok()?);
+    pools.insert(threads, Arc::clone(&pool));
+    Some(pool)
+}
+
+/// One digest over a set of files, e.g. as a cache key for a group of config files.
+///
+/// Paths are deduplicated and sorted by their UTF-8 bytes; the result is the
+/// digest of `<path>\0<file hash>\n` for each path in that order, using the
+/// same algorithm for the per-file and combined hashes. Paths are hashed as
+/// given, so pass relative paths for a key that is stable across checkouts.
+/// Fails if any file cannot be hashed.
+#[napi(catch_unwind)]
+pub fn hash_files_combined(
+    file_paths: Vec<String>,
+    options: Option<HashOptions>,
+) -> napi::Result<String> {
+    let options = options.unwrap_or_default();
+    let algorithm = options.algorithm.unwrap_or_default();
+    let mut paths = file_paths;
+    paths.sort_unstable();
+    paths.dedup();
+
+    // Buffer encoding moves digests out of `hash`; single strings fall back to hex
+    let file_options = match options.encoding {
+        Some(DigestEncoding::Buffer) => HashOptions {
+            encoding: Some(DigestEncoding::Hex),
+            ..options.clone()
+        },
+        _ => options.clone(),
+    };
+    let batch = hash_files(paths, Some(file_options))?;
+    if let Some(failure) = batch.failed.first() {
+        return Err(napi::Error::from_reason(format!(
+            "Failed to read file {}: {}",
+            failure.path, failure.message
+        )));
+    }
+    // hash_files preserves input order, so `hashes` is already canonical
+    let mut digester = Digester::new(algorithm);
+    for entry in &batch.hashes {
+        digester.update(entry.path.as_bytes());
+        digester.update(b"\0");
+        digester.update(entry.hash.as_bytes());
+        digester.update(b"\n");
+    }
+    Ok(options.format_digest(digester.finalize()))
+}
+
+/// `hash_entry` that reuses `cache` when the file's size and mtime are unchanged.
+/// Always fills `size`/`mtime` so the caller can update the cache.
+fn hash_entry_cached(
+    path: &Path,
+    options: &HashOptions,
+    cache: &HashCache,
+    throttle: Option<&Throttle>,
+) -> io::Result<FileHash> {
+    let options = HashOptions {
+        include_stat: Some(true),
+        ..options.clone()
+    };
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_symlink() && options.symlinks == Some(SymlinkMode::HashTarget) {
+        return hash_entry(path, &options);
+    }
+    let metadata = if metadata.is_symlink() { fs::metadata(path)? } else { metadata };
+    let path_string = path.to_string_lossy().into_owned();
+    let mtime = mtime_millis(&metadata);
+    match cache.get(&path_string, metadata.len(), mtime) {
+        Some(hash) => Ok(FileHash {
+            hash: hash.to_string(),
+            path: path_string,
+            symlink_target: None,
+            broken_symlink: None,
+            sampled: options.samples(metadata.len()).then_some(true),
+            digest: None,
+            size: Some(metadata.len() as i64),
+            mtime: Some(mtime),
+        }),
+        None => {
+            if let Some(throttle) = throttle {
+                throttle.acquire(metadata.len());
+            }
+            hash_entry(path, &options)
+        }
+    }
+}
+
+/// `options`, unless they combine multihash output with an installed hash key
+pub(crate) fn checked_options(options: Option<HashOptions>) -> napi::Result<HashOptions> {
+    let options = options.unwrap_or_default();
+    if options.multihash == Some(true) && current_hash_key().is_some() {
+        return Err(napi::Error::from_reason(
+            "Multihash output cannot describe keyed digests; unset multihash or the hash key",
+        ));
+    }
+    Ok(options)
+}
+
+/// `options` for the `sha256*` functions, which fail rather than hash with
+/// another requested algorithm
+fn with_sha256(options: Option<HashOptions>) -> napi::Result<HashOptions> {
+    let options = checked_options(options)?;
+    match options.algorithm {
+        None | Some(HashAlgorithm::Sha256) => Ok(HashOptions {
+            algorithm: Some(HashAlgorithm::Sha256),
+            ..options
+        }),
+        Some(other) => Err(napi::Error::from_reason(format!(
+            "The sha256 functions only hash with SHA-256, not {:?}; use hashFile(s) instead",
+            other
+        ))),
+    }
+}
+
+/// Indexed entries that no longer match the files on disk
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct StaleEntries {
+    /// Files whose current content hash differs from the indexed hash
+    pub modified: Vec<String>,
+    /// Files that no longer exist or cannot be read
+    pub missing: Vec<String>,
+}
+
+/// Re-hash indexed files under `root_path` and report entries whose indexed hash is out of date
+/// (e.g. after missed watcher events). Entry paths are relative to `root_path`, and
+/// `options` must match the settings the indexed hashes were produced with.
+/// Intended to run periodically; the caller reindexes the returned paths.
+#[napi(catch_unwind)]
+pub fn find_stale_entries(
+    root_path: String,
+    entries: Vec<FileHashEntry>,
+    options: Option<HashOptions>,
+) -> napi::Result<StaleEntries> {
+    use rayon::prelude::*;
+
+    let root = Path::new(&root_path);
+    let options = checked_options(options)?;
+    let checked: Vec<(&FileHashEntry, Option<String>)> = entries
+        .par_iter()
+        .map(|entry| (entry, hash_entry(&root.join(&entry.path), &options).ok().map(|h| h.hash)))
+        .collect();
+
+    let mut stale = StaleEntries::default();
+    for (entry, current) in checked {
+        match current {
+            None => stale.missing.push(entry.path.clone()),
+            Some(hash) if hash != entry.hash => stale.modified.push(entry.path.clone()),
+            _ => {} // up to date
+        }
+    }
+    Ok(stale)
 }
 
 #[napi(object)]
@@ -46,4 +574,570 @@ pub fn sha256_hash_files(file_paths: Vec<String>) -> Vec<FileHash> {
 pub struct FileHash {
     pub path: String,
     pub hash: String,
+    /// Link target, set when the path is a symlink hashed with `SymlinkMode::HashTarget`
+    pub symlink_target: Option<String>,
+    /// Set when the path is a symlink whose target does not exist
+    pub broken_symlink: Option<bool>,
+    /// Set when the hash covers only sampled regions of the file (see `sample_size`)
+    pub sampled: Option<bool>,
+    /// Raw digest bytes, set instead of `hash` with `DigestEncoding::Buffer`
+    pub digest: Option<Buffer>,
+    /// Size in bytes at the time of hashing (with `include_stat`)
+    pub size: Option<i64>,
+    /// Modification time in milliseconds since the Unix epoch at the time of hashing (with `include_stat`)
+    pub mtime: Option<f64>,
+}
+
+/// Result of `hash_files`
+#[napi(object)]
+#[derive(Clone)]
+pub struct HashFilesResult {
+    /// Successfully hashed files, in input order
+    pub hashes: Vec<FileHash>,
+    /// Files that could not be hashed, in input order
+    pub failed: Vec<HashFailure>,
+}
+
+/// Why a file could not be hashed
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, PartialEq, Eq)]
+pub enum HashErrorKind {
+    NotFound,
+    PermissionDenied,
+    /// The path is a symlink whose target does not exist
+    BrokenSymlink,
+    IsDirectory,
+    Other,
+}
+
+/// A file that could not be hashed
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct HashFailure {
+    pub path: String,
+    pub error_kind: HashErrorKind,
+    pub message: String,
+}
+
+impl HashFailure {
+    pub(crate) fn new(path: &str, err: &io::Error) -> Self {
+        let error_kind = match err.kind() {
+            io::ErrorKind::NotFound => {
+                if fs::symlink_metadata(path).is_ok_and(|m| m.is_symlink()) {
+                    HashErrorKind::BrokenSymlink
+                } else {
+                    HashErrorKind::NotFound
+                }
+            }
+            io::ErrorKind::PermissionDenied => HashErrorKind::PermissionDenied,
+            _ if fs::metadata(path).is_ok_and(|m| m.is_dir()) => HashErrorKind::IsDirectory,
+            _ => HashErrorKind::Other,
+        };
+        HashFailure {
+            path: path.to_string(),
+            error_kind,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Hash one path according to `options`, including its symlink handling
+pub(crate) fn hash_entry(path: &Path, options: &HashOptions) -> io::Result<FileHash> {
+    let path_string = path.to_string_lossy().into_owned();
+    let include_stat = options.include_stat == Some(true);
+    if options.symlinks == Some(SymlinkMode::HashTarget) {
+        let link_metadata = fs::symlink_metadata(path)?;
+        if link_metadata.is_symlink() {
+            let target = fs::read_link(path)?.to_string_lossy().into_owned();
+            let mut digester =
+                Digester::for_content(options.algorithm.unwrap_or_default(), target.len() as u64);
+            digester.update(target.as_bytes());
+            return Ok(FileHash {
+                path: path_string,
+                hash: options.format_digest(digester.finalize()),
+                broken_symlink: Some(fs::metadata(path).is_err()),
+                symlink_target: Some(target),
+                sampled: None,
+                digest: None,
+                size: include_stat.then_some(link_metadata.len() as i64),
+                mtime: include_stat.then(|| mtime_millis(&link_metadata)),
+            });
+        }
+    }
+    let (hash, metadata) = hash_file_contents(path, options)?;
+    Ok(FileHash {
+        path: path_string,
+        hash,
+        symlink_target: None,
+        broken_symlink: None,
+        sampled: options.samples(metadata.len()).then_some(true),
+        digest: None,
+        size: include_stat.then_some(metadata.len() as i64),
+        mtime: include_stat.then(|| mtime_millis(&metadata)),
+    })
+}
+
+/// Digest of a file's contents, formatted per `options` and streamed in fixed-size chunks so
+/// memory use does not grow with file size. Also returns the metadata of
+/// the open handle, re-checked after hashing so it describes exactly the
+/// content that was hashed; the read is retried if the file changes meanwhile.
+pub(crate) fn hash_file_contents(
+    path: &Path,
+    options: &HashOptions,
+) -> io::Result<(String, fs::Metadata)> {
+    for _ in 0..HASH_ATTEMPTS {
+        let file = fs::File::open(path)?;
+        let before = file.metadata()?;
+        if options.read_ahead == Some(true) {
+            advise_read_ahead(&file, before.len());
+        }
+        let digest = hash_open_file(&file, before.len(), options)?;
+        let after = file.metadata()?;
+        if before.len() == after.len() && before.modified().ok() == after.modified().ok() {
+            return Ok((options.format_digest(digest), before));
+        }
+    }
+    Err(io::Error::other("file kept changing while hashing"))
+}
+
+/// Hint sequential access and request the whole file be read in the background.
+/// Purely advisory: failures are ignored and hashing proceeds with plain reads.
+#[cfg(target_os = "linux")]
+fn advise_read_ahead(file: &fs::File, len: u64) {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    // SAFETY: `fd` is a valid open descriptor for the lifetime of `file`;
+    // posix_fadvise does not touch memory.
+    unsafe {
+        libc::posix_fadvise(fd, 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+        libc::posix_fadvise(fd, 0, len as libc::off_t, libc::POSIX_FADV_WILLNEED);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_read_ahead(_file: &fs::File, _len: u64) {}
+
+fn hash_open_file(file: &fs::File, len: u64, options: &HashOptions) -> io::Result<Vec<u8>> {
+    let algorithm = options.algorithm.unwrap_or_default();
+    if options.samples(len) {
+        return hash_sampled(file, len, options.sample_bytes().unwrap_or_default(), algorithm);
+    }
+    if options.normalize_line_endings == Some(true) && !starts_binary(file)? {
+        return hash_normalized(file, algorithm);
+    }
+    if options.use_mmap == Some(true) {
+        if let Some(map) = map_file(file) {
+            let mut digester = Digester::for_content(algorithm, map.len() as u64);
+            digester.update_large(&map);
+            return Ok(digester.finalize());
+        }
+    }
+    let mut digester = Digester::for_content(algorithm, len);
+    let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, file);
+    let copied = io::copy(&mut reader, &mut digester)?;
+    if algorithm.is_git() && copied != len {
+        // The git header already committed to the old length
+        return Err(io::Error::other("file changed while hashing"));
+    }
+    Ok(digester.finalize())
+}
+
+/// Digest of `"sampled\0"`, the length as u64 LE, and `sample` bytes from each end.
+/// The prefix keeps sampled hashes from ever equalling a full-content hash.
+fn hash_sampled(
+    mut file: &fs::File,
+    len: u64,
+    sample: u32,
+    algorithm: HashAlgorithm,
+) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0u8; sample as usize];
+    let mut digester = Digester::new(algorithm);
+    digester.update(b"sampled\0");
+    digester.update(&len.to_le_bytes());
+    file.read_exact(&mut buffer)?;
+    digester.update(&buffer);
+    file.seek(SeekFrom::Start(len - sample as u64))?;
+    file.read_exact(&mut buffer)?;
+    digester.update(&buffer);
+    Ok(digester.finalize())
+}
+
+/// Whether the file looks binary (NUL in the first 8000 bytes, git's heuristic).
+/// Leaves the file positioned at the start.
+fn starts_binary(mut file: &fs::File) -> io::Result<bool> {
+    let mut head = Vec::with_capacity(8000);
+    file.take(8000).read_to_end(&mut head)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(head.contains(&0))
+}
+
+/// Hash a text file with CRLF rewritten to LF
+fn hash_normalized(mut file: &fs::File, algorithm: HashAlgorithm) -> io::Result<Vec<u8>> {
+    let mut normalized_len = 0;
+    if algorithm.is_git() {
+        // The git header needs the normalized length up front
+        let mut counter = CrlfToLf::new(ByteCounter(0));
+        io::copy(&mut BufReader::with_capacity(READ_BUFFER_SIZE, file), &mut counter)?;
+        normalized_len = counter.finish()?.0;
+        file.seek(SeekFrom::Start(0))?;
+    }
+    let mut normalizer = CrlfToLf::new(Digester::for_content(algorithm, normalized_len));
+    io::copy(&mut BufReader::with_capacity(READ_BUFFER_SIZE, file), &mut normalizer)?;
+    Ok(normalizer.finish()?.finalize())
+}
+
+/// Writer adapter that rewrites CRLF to LF. A trailing CR is held back until
+/// the next write (or `finish`) shows whether an LF follows it.
+struct CrlfToLf<W: Write> {
+    inner: W,
+    pending_cr: bool,
+}
+
+impl<W: Write> CrlfToLf<W> {
+    fn new(inner: W) -> Self {
+        CrlfToLf {
+            inner,
+            pending_cr: false,
+        }
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        if self.pending_cr {
+            self.inner.write_all(b"\r")?;
+        }
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for CrlfToLf<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if std::mem::take(&mut self.pending_cr) && buf[0] != b'\n' {
+            self.inner.write_all(b"\r")?;
+        }
+        let mut start = 0;
+        for i in 0..buf.len() {
+            if buf[i] != b'\r' {
+                continue;
+            }
+            match buf.get(i + 1) {
+                Some(b'\n') => {
+                    self.inner.write_all(&buf[start..i])?;
+                    start = i + 1;
+                }
+                Some(_) => {}
+                None => {
+                    self.inner.write_all(&buf[start..i])?;
+                    self.pending_cr = true;
+                    start = buf.len();
+                }
+            }
+        }
+        self.inner.write_all(&buf[start..])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Write sink that only counts bytes
+struct ByteCounter(u64);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Workspace secret installed by `init_hash_key`, with per-algorithm key material
+pub(crate) struct HashKey {
+    secret: Vec<u8>,
+    blake3_key: [u8; 32],
+    xxh3_seed: u64,
+    /// Short non-secret fingerprint, used to keep keyed and unkeyed cache entries apart
+    pub(crate) id: String,
+}
+
+impl HashKey {
+    fn derive(secret: Vec<u8>) -> Self {
+        let blake3_key = blake3::derive_key("codebase-native 2024 workspace hash key", &secret);
+        let id_bytes = blake3::derive_key("codebase-native 2024 workspace hash key id", &secret);
+        HashKey {
+            xxh3_seed: u64::from_le_bytes(blake3_key[..8].try_into().unwrap()),
+            id: hex::encode(&id_bytes[..8]),
+            blake3_key,
+            secret,
+        }
+    }
+}
+
+/// Set at most once, so no batch ever mixes keyed and unkeyed hashes
+static HASH_KEY: OnceLock<HashKey> = OnceLock::new();
+
+/// The installed hash key, if any
+pub(crate) fn current_hash_key() -> Option<&'static HashKey> {
+    HASH_KEY.get()
+}
+
+/// Install a workspace secret that keys every subsequent digest (HMAC for the
+/// SHA-based algorithms, keyed mode for BLAKE3), so a server storing the hashes
+/// cannot dictionary-attack well-known file contents. XXH3 is only seeded from
+/// the secret and offers no such protection.
+///
+/// Call it once at startup, before hashing anything: the key applies to file
+/// and content hashes as well as directory hashes in Merkle trees, and cannot
+/// be changed or removed. Installing a different secret later fails, the same
+/// one is a no-op. The key is process-wide: it also applies to instances
+/// loaded in other workers.
+#[napi(catch_unwind)]
+pub fn init_hash_key(secret: Buffer) -> napi::Result<()> {
+    if secret.is_empty() {
+        return Err(napi::Error::from_reason("Hash key must not be empty"));
+    }
+    let secret = secret.to_vec();
+    let installed = HASH_KEY.get_or_init(|| HashKey::derive(secret.clone()));
+    if installed.secret != secret {
+        return Err(napi::Error::from_reason("A different hash key is already installed"));
+    }
+    Ok(())
+}
+
+/// Incremental hasher over any supported `HashAlgorithm`
+pub(crate) enum Digester {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<Xxh3>),
+    Sha1(Sha1),
+    HmacSha256(Box<Hmac<Sha256>>),
+    HmacSha1(Box<Hmac<Sha1>>),
+}
+
+impl Digester {
+    /// Raw digester, used for tree-level hashes; keyed if a hash key is installed
+    pub(crate) fn new(algorithm: HashAlgorithm) -> Self {
+        match current_hash_key() {
+            Some(key) => Digester::keyed(algorithm, key),
+            None => match algorithm {
+                HashAlgorithm::Sha256 | HashAlgorithm::GitSha256 => Digester::Sha256(Sha256::new()),
+                HashAlgorithm::Blake3 => Digester::Blake3(Box::new(blake3::Hasher::new())),
+                HashAlgorithm::Xxh3 => Digester::Xxh3(Box::new(Xxh3::new())),
+                HashAlgorithm::GitSha1 => Digester::Sha1(Sha1::new()),
+            },
+        }
+    }
+
+    fn keyed(algorithm: HashAlgorithm, key: &HashKey) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 | HashAlgorithm::GitSha256 => Digester::HmacSha256(Box::new(
+                Hmac::new_from_slice(&key.secret).expect("HMAC accepts keys of any length"),
+            )),
+            HashAlgorithm::Blake3 => {
+                Digester::Blake3(Box::new(blake3::Hasher::new_keyed(&key.blake3_key)))
+            }
+            HashAlgorithm::Xxh3 => Digester::Xxh3(Box::new(Xxh3::with_seed(key.xxh3_seed))),
+            HashAlgorithm::GitSha1 => Digester::HmacSha1(Box::new(
+                Hmac::new_from_slice(&key.secret).expect("HMAC accepts keys of any length"),
+            )),
+        }
+    }
+
+    /// Digester for `len` bytes of content, prefixed with the git blob header when required
+    pub(crate) fn for_content(algorithm: HashAlgorithm, len: u64) -> Self {
+        let mut digester = Digester::new(algorithm);
+        if algorithm.is_git() {
+            digester.update(format!("blob {}\0", len).as_bytes());
+        }
+        digester
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Digester::Sha256(h) => h.update(data),
+            Digester::Blake3(h) => {
+                h.update(data);
+            }
+            Digester::Xxh3(h) => h.update(data),
+            Digester::Sha1(h) => h.update(data),
+            Digester::HmacSha256(h) => h.update(data),
+            Digester::HmacSha1(h) => h.update(data),
+        }
+    }
+
+    /// Like `update`, but lets BLAKE3 spread large inputs across the rayon pool
+    pub(crate) fn update_large(&mut self, data: &[u8]) {
+        match self {
+            Digester::Blake3(h) => {
+                h.update_rayon(data);
+            }
+            _ => self.update(data),
+        }
+    }
+
+    pub(crate) fn finalize(self) -> Vec<u8> {
+        match self {
+            Digester::Sha256(h) => h.finalize().to_vec(),
+            Digester::Blake3(h) => h.finalize().as_bytes().to_vec(),
+            // Canonical (big-endian) XXH3 representation, as printed by `xxhsum -H3`
+            Digester::Xxh3(h) => h.digest().to_be_bytes().to_vec(),
+            Digester::Sha1(h) => h.finalize().to_vec(),
+            Digester::HmacSha256(h) => h.finalize().into_bytes().to_vec(),
+            Digester::HmacSha1(h) => h.finalize().into_bytes().to_vec(),
+        }
+    }
+
+    pub(crate) fn finalize_hex(self) -> String {
+        hex::encode(self.finalize())
+    }
+}
+
+impl Write for Digester {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Map a file read-only, or `None` when it is too small to benefit or cannot be mapped
+fn map_file(file: &fs::File) -> Option<Mmap> {
+    let len = file.metadata().ok()?.len();
+    if len < READ_BUFFER_SIZE as u64 {
+        return None;
+    }
+    // SAFETY: the map is read-only and dropped before returning to JS. A file
+    // truncated concurrently by another process can fault, the same caveat as
+    // every mmap-based reader; callers opt in explicitly via `use_mmap`.
+    unsafe { Mmap::map(file) }.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Fresh scratch directory below the system temp dir
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "codebase-native-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn combined_hash_with_buffer_encoding_covers_content() {
+        let dir = scratch("combined");
+        let path = dir.join("a.txt");
+        let paths = vec![path.to_string_lossy().into_owned()];
+        let options = HashOptions {
+            encoding: Some(DigestEncoding::Buffer),
+            ..Default::default()
+        };
+
+        fs::write(&path, "one").unwrap();
+        let before = hash_files_combined(paths.clone(), Some(options.clone())).unwrap();
+        let hex = hash_files_combined(paths.clone(), None).unwrap();
+        fs::write(&path, "two").unwrap();
+        let after = hash_files_combined(paths, Some(options)).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_ne!(before, after);
+        assert_eq!(before, hex);
+    }
+
+    #[test]
+    fn sha256_functions_reject_other_algorithms() {
+        let dir = scratch("sha256");
+        let path = dir.join("a.txt");
+        fs::write(&path, "abc").unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let options = |algorithm| HashOptions {
+            algorithm: Some(algorithm),
+            ..Default::default()
+        };
+
+        let sha256 = sha256_hash_file(path.clone(), Some(options(HashAlgorithm::Sha256)));
+        let blake3 = sha256_hash_file(path.clone(), Some(options(HashAlgorithm::Blake3)));
+        let batch = sha256_hash_files(vec![path], Some(options(HashAlgorithm::Blake3)));
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(sha256.unwrap(), sha256_hash("abc".to_string()));
+        assert!(blake3.is_err());
+        assert!(batch.is_err());
+    }
+
+    #[test]
+    fn sha256_hash_files_reports_missing_files() {
+        let dir = scratch("sha256-missing");
+        let (present, missing) = (dir.join("a.txt"), dir.join("missing.txt"));
+        fs::write(&present, "abc").unwrap();
+        let paths = [&present, &missing].map(|p| p.to_string_lossy().into_owned());
+
+        let batch = sha256_hash_files(paths.to_vec(), None).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(batch.hashes.len(), 1);
+        assert_eq!(batch.failed.len(), 1);
+        assert_eq!(batch.failed[0].path, paths[1]);
+        assert_eq!(batch.failed[0].error_kind, HashErrorKind::NotFound);
+    }
+
+    #[test]
+    fn hash_pools_are_reused_per_concurrency() {
+        let two = hash_pool(2).unwrap();
+        assert!(Arc::ptr_eq(&two, &hash_pool(2).unwrap()));
+        assert!(!Arc::ptr_eq(&two, &hash_pool(3).unwrap()));
+        assert_eq!(two.current_num_threads(), 2);
+    }
+
+    #[test]
+    fn zero_sample_size_hashes_whole_files() {
+        let dir = scratch("sample");
+        let (a, b) = (dir.join("a.bin"), dir.join("b.bin"));
+        fs::write(&a, "first file").unwrap();
+        fs::write(&b, "other file").unwrap();
+        let options = HashOptions {
+            sample_size: Some(0),
+            ..Default::default()
+        };
+
+        let sampled = hash_entry(&a, &options).unwrap();
+        let other = hash_entry(&b, &options).unwrap();
+        let full = hash_entry(&a, &HashOptions::default()).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(sampled.sampled, None);
+        assert_eq!(sampled.hash, full.hash);
+        assert_ne!(sampled.hash, other.hash);
+    }
+
+    #[test]
+    fn cache_variant_tells_sample_sizes_apart() {
+        let variant = |sample_size| {
+            HashOptions {
+                sample_size,
+                ..Default::default()
+            }
+            .cache_variant()
+        };
+        assert_eq!(variant(None), variant(Some(0)));
+        assert_ne!(variant(None), variant(Some(1)));
+        assert_ne!(variant(Some(1)), variant(Some(2)));
+    }
 }
@@ -1,6 +1,23 @@
-#[macro_use]
-extern crate napi_derive;
-
+pub mod analysis;
+mod cache;
+#[cfg(feature = "cdc")]
+pub mod cdc;
+pub mod compat;
+#[cfg(feature = "zstd")]
+pub mod compress;
+pub mod crash;
+pub mod fingerprint;
 pub mod hasher;
 pub mod merkle;
+pub mod packed;
+pub mod quality;
+pub mod remote;
 pub mod scanner;
+pub mod selftest;
+pub mod stat;
+pub mod style;
+mod throttle;
+pub mod tree;
+pub mod treefile;
+pub mod vectors;
+pub mod version;
@@ -1,6 +1,12 @@
+use crate::cache::push_varint;
+use crate::crash::trace_event;
+use crate::hasher::{Digester, HashAlgorithm, HashOptions};
+use crate::scanner::{scan_and_hash, ScanOptions};
+use crate::treefile::serialize_tree;
+use napi::bindgen_prelude::Buffer;
+use napi::{JsFunction, JsUnknown, ValueType};
 use napi_derive::napi;
-use sha2::{Digest, Sha256};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 /// A node in the Merkle tree
 #[napi(object)]
@@ -8,12 +14,27 @@ use std::collections::BTreeMap;
 pub struct MerkleNode {
     /// Relative path of this node (file or directory)
     pub path: String,
-    /// SHA-256 hash
+    /// Hex digest: the file hash as given for files (salted in a namespace, see
+    /// `leaf_hash`), computed with `algorithm` for directories
     pub hash: String,
     /// Whether this is a file (leaf) or directory (internal node)
     pub is_file: bool,
     /// Children paths (empty for files)
     pub children: Vec<String>,
+    /// Algorithm the hash was computed with (absent in trees saved before it was recorded,
+    /// which are treated as SHA-256)
+    pub algorithm: Option<HashAlgorithm>,
+    /// Set on the tree's root directory only; trees saved before the marker existed
+    /// fall back to the node at "."
+    pub is_root: Option<bool>,
+    /// File size in bytes; for directories the total of the files below with a known size
+    pub size: Option<i64>,
+    /// 1 for files; for directories the number of files below
+    pub file_count: Option<u32>,
+    /// Directory hash scheme (see `MerkleOptions::hash_scheme`); absent means 1
+    pub hash_scheme: Option<u32>,
+    /// Workspace namespace mixed into the hashes (see `MerkleOptions::namespace`)
+    pub namespace: Option<String>,
 }
 
 /// Result of diffing two Merkle trees
@@ -26,109 +47,292 @@ pub struct MerkleDiff {
     pub removed: Vec<String>,
     /// Files that were modified (exist in both but hash differs)
     pub modified: Vec<String>,
+    /// Directories whose hash differs or that exist on one side only, sorted
+    /// (with `DiffOptions::include_directories`)
+    pub changed_directories: Option<Vec<String>>,
+    /// Files moved without content changes (with `DiffOptions::detect_renames`);
+    /// these paths are not listed in `added` or `removed`
+    pub renamed: Option<Vec<FileRename>>,
 }
 
-/// Build a Merkle tree from a list of (relative_path, file_content_hash) pairs.
-/// Returns a list of all nodes (files + directories + root).
-#[napi]
-pub fn build_merkle_tree(file_hashes: Vec<FileHashEntry>) -> Vec<MerkleNode> {
+/// A file that moved from one path to another with identical content
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct FileRename {
+    pub from: String,
+    pub to: String,
+    /// File node hash shared by both paths
+    pub hash: String,
+}
+
+/// Options for `diff_merkle_trees`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct DiffOptions {
+    /// Also report changed directories, so callers can prune traversal or
+    /// summarize changes per directory
+    pub include_directories: Option<bool>,
+    /// Pair removed and added files with identical content hashes as renames.
+    /// With several candidates, paths are paired in sorted order.
+    pub detect_renames: Option<bool>,
+}
+
+/// Options for `build_merkle_tree`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct MerkleOptions {
+    /// Digest algorithm for directory hashes (default SHA-256); should match the
+    /// algorithm used for the file hashes
+    pub algorithm: Option<HashAlgorithm>,
+    /// How directory hashes are derived from their children (default 1):
+    /// 1 digests the sorted child hashes; 2 also covers child names and tags
+    /// files and directories, so no file can stand in for a directory
+    pub hash_scheme: Option<u32>,
+    /// Salt mixed into every node hash, e.g. a workspace id, so trees of
+    /// different workspaces sharing a backend never share a hash. File nodes
+    /// hash their content hash with the salt (see `leaf_hash`), so identical
+    /// files do not reveal themselves across workspaces. Empty means no namespace.
+    pub namespace: Option<String>,
+}
+
+/// Options for `hash_directory`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct HashDirectoryOptions {
+    /// Which files to include (same rules as `scan_directory_with_options`)
+    pub scan: Option<ScanOptions>,
+    /// How files are hashed; the algorithm is also used for directory hashes
+    pub hash: Option<HashOptions>,
+    /// Directory hash scheme, see `MerkleOptions::hash_scheme`
+    pub hash_scheme: Option<u32>,
+    /// See `MerkleOptions::namespace`
+    pub namespace: Option<String>,
+}
+
+/// Result of `hash_directory`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct DirectoryDigest {
+    /// Merkle root hash; equals `get_root_hash` of the tree built from the same files
+    pub root_hash: String,
+    pub file_count: u32,
+}
+
+/// Scan, hash and fold a directory into its Merkle root in one call, for cheap
+/// "has anything changed?" checks. An empty directory hashes like a directory
+/// with no children.
+#[napi(catch_unwind)]
+pub fn hash_directory(
+    root_path: String,
+    options: Option<HashDirectoryOptions>,
+) -> napi::Result<DirectoryDigest> {
+    let options = options.unwrap_or_default();
+    let scheme = options.hash_scheme.unwrap_or(LEGACY_HASH_SCHEME);
+    let namespace = options.namespace.clone().filter(|n| !n.is_empty());
+    let (nodes, algorithm) = tree_from_directory(root_path, options)?;
+    let file_count = nodes.iter().filter(|n| n.is_file).count() as u32;
+    let root_hash = root_node(&nodes)
+        .map(|node| node.hash.clone())
+        .unwrap_or_else(|| directory_hash(Vec::new(), algorithm, scheme, namespace.as_deref()));
+    Ok(DirectoryDigest {
+        root_hash,
+        file_count,
+    })
+}
+
+/// Scan, hash and build the Merkle tree of a directory in one call, without
+/// moving the file list and hashes across the JS boundary in between.
+/// Same result as `build_merkle_tree` over the files of `scan_and_hash`; files
+/// that cannot be hashed are left out of the tree.
+#[napi(catch_unwind)]
+pub fn build_merkle_tree_from_directory(
+    root_path: String,
+    options: Option<HashDirectoryOptions>,
+) -> napi::Result<Vec<MerkleNode>> {
+    let (nodes, _) = tree_from_directory(root_path, options.unwrap_or_default())?;
+    Ok(nodes)
+}
+
+/// `build_merkle_tree_from_directory` returning the tree in the `serialize_tree`
+/// format, so large trees cross the JS boundary as a single buffer
+#[napi(catch_unwind)]
+pub fn build_serialized_merkle_tree_from_directory(
+    root_path: String,
+    options: Option<HashDirectoryOptions>,
+) -> napi::Result<Buffer> {
+    let (nodes, _) = tree_from_directory(root_path, options.unwrap_or_default())?;
+    serialize_tree(nodes)
+}
+
+fn tree_from_directory(
+    root_path: String,
+    options: HashDirectoryOptions,
+) -> napi::Result<(Vec<MerkleNode>, HashAlgorithm)> {
+    let algorithm = options
+        .hash
+        .as_ref()
+        .and_then(|h| h.algorithm)
+        .unwrap_or_default();
+    let scan = scan_and_hash(root_path, options.scan, options.hash)?;
+    let entries = scan
+        .files
+        .into_iter()
+        .map(|f| FileHashEntry {
+            path: f.path,
+            hash: f.hash,
+            is_directory: None,
+            size: Some(f.size),
+        })
+        .collect();
+    let tree_options = MerkleOptions {
+        algorithm: Some(algorithm),
+        hash_scheme: options.hash_scheme,
+        namespace: options.namespace,
+    };
+    Ok((build_merkle_tree(entries, Some(tree_options))?, algorithm))
+}
+
+/// Build a Merkle tree from a list of (relative_path, file_content_hash) pairs,
+/// plus optional explicit directory entries. Returns a list of all nodes
+/// (files + directories + root). Empty directories hash to a fixed sentinel
+/// (see `directory_hash`). Paths are normalized
+/// with `normalize_tree_path`, so Windows-style input yields the same tree.
+#[napi(catch_unwind)]
+pub fn build_merkle_tree(
+    file_hashes: Vec<FileHashEntry>,
+    options: Option<MerkleOptions>,
+) -> napi::Result<Vec<MerkleNode>> {
+    trace_event(format!("build tree from {} entries", file_hashes.len()));
+    let options = options.unwrap_or_default();
+    let algorithm = options.algorithm.unwrap_or_default();
+    let scheme = check_scheme(options.hash_scheme.unwrap_or(LEGACY_HASH_SCHEME))?;
+    let namespace = options.namespace.filter(|n| !n.is_empty());
+
     // Group files by directory
     let mut dir_children: BTreeMap<String, Vec<String>> = BTreeMap::new();
     let mut nodes: BTreeMap<String, MerkleNode> = BTreeMap::new();
 
     // Insert all file (leaf) nodes
     for fh in &file_hashes {
+        let path = normalize_tree_path(&fh.path);
+        if fh.is_directory == Some(true) {
+            // Explicit directory: registered even when no file lies below it
+            let dir = if path.is_empty() { ".".to_string() } else { path };
+            dir_children.entry(dir.clone()).or_default();
+            register_ancestors(&mut dir_children, dir);
+            continue;
+        }
         nodes.insert(
-            fh.path.clone(),
+            path.clone(),
             MerkleNode {
-                path: fh.path.clone(),
-                hash: fh.hash.clone(),
+                path: path.clone(),
+                hash: leaf_hash(fh.hash.clone(), algorithm, namespace.as_deref()),
                 is_file: true,
                 children: vec![],
+                algorithm: Some(algorithm),
+                is_root: None,
+                size: fh.size,
+                file_count: Some(1),
+                hash_scheme: Some(scheme),
+                namespace: namespace.clone(),
             },
         );
 
         // Register this file under its parent directory
-        let parent = parent_path(&fh.path);
-        dir_children
-            .entry(parent.clone())
-            .or_default()
-            .push(fh.path.clone());
-
-        // Ensure all ancestor directories are registered
-        let mut current_parent = parent;
-        loop {
-            let grandparent = parent_path(&current_parent);
-            if grandparent == current_parent {
-                break; // reached root
-            }
-            dir_children
-                .entry(grandparent.clone())
-                .or_default();
-            // Make sure current_parent is a child of grandparent
-            let siblings = dir_children.entry(grandparent.clone()).or_default();
-            if !siblings.contains(&current_parent) {
-                siblings.push(current_parent.clone());
-            }
-            current_parent = grandparent;
-        }
+        let parent = parent_path(&path);
+        dir_children.entry(parent.clone()).or_default().push(path);
+        register_ancestors(&mut dir_children, parent);
     }
 
     // Build directory nodes bottom-up (BTreeMap is sorted, process deepest paths first)
     // Collect all directory paths and sort by depth descending
     let mut dir_paths: Vec<String> = dir_children.keys().cloned().collect();
-    dir_paths.sort_by(|a, b| {
-        let depth_a = a.matches('/').count();
-        let depth_b = b.matches('/').count();
-        depth_b.cmp(&depth_a) // deepest first
-    });
+    dir_paths.sort_by_key(|path| std::cmp::Reverse(dir_depth(path))); // deepest first
 
     for dir_path in &dir_paths {
         let children = dir_children.get(dir_path).cloned().unwrap_or_default();
-        let mut child_hashes: Vec<String> = Vec::new();
-
-        for child in &children {
-            if let Some(node) = nodes.get(child) {
-                child_hashes.push(node.hash.clone());
-            }
-        }
-
-        // Sort child hashes for deterministic tree
-        child_hashes.sort();
-        let combined = child_hashes.join("");
-        let mut hasher = Sha256::new();
-        hasher.update(combined.as_bytes());
-        let dir_hash = hex::encode(hasher.finalize());
+        let entries = children
+            .iter()
+            .filter_map(|c| nodes.get(c))
+            .map(DirEntry::of)
+            .collect();
+        let hash = directory_hash(entries, algorithm, scheme, namespace.as_deref());
+        let (size, file_count) = aggregate(children.iter().filter_map(|c| nodes.get(c)));
 
         nodes.insert(
             dir_path.clone(),
             MerkleNode {
                 path: dir_path.clone(),
-                hash: dir_hash,
+                hash,
                 is_file: false,
                 children,
+                algorithm: Some(algorithm),
+                is_root: (dir_path == ".").then_some(true),
+                size,
+                file_count: Some(file_count),
+                hash_scheme: Some(scheme),
+                namespace: namespace.clone(),
             },
         );
     }
 
-    nodes.into_values().collect()
+    Ok(nodes.into_values().collect())
+}
+
+/// Ensure all ancestor directories of `dir` are registered, each listing the next
+fn register_ancestors(dir_children: &mut BTreeMap<String, Vec<String>>, dir: String) {
+    let mut current_parent = dir;
+    loop {
+        let grandparent = parent_path(&current_parent);
+        if grandparent == current_parent {
+            break; // reached root
+        }
+        // Make sure current_parent is a child of grandparent
+        let siblings = dir_children.entry(grandparent.clone()).or_default();
+        if !siblings.contains(&current_parent) {
+            siblings.push(current_parent.clone());
+        }
+        current_parent = grandparent;
+    }
 }
 
 /// Diff two Merkle trees (represented as flat lists of nodes).
 /// Returns added, removed, and modified FILE paths.
-#[napi]
-pub fn diff_merkle_trees(old_nodes: Vec<MerkleNode>, new_nodes: Vec<MerkleNode>) -> MerkleDiff {
-    let old_files: BTreeMap<String, String> = old_nodes
-        .iter()
-        .filter(|n| n.is_file)
-        .map(|n| (n.path.clone(), n.hash.clone()))
-        .collect();
+/// Fails if the trees were hashed with different algorithms, hash schemes or namespaces.
+#[napi(catch_unwind)]
+pub fn diff_merkle_trees(
+    old_nodes: Vec<MerkleNode>,
+    new_nodes: Vec<MerkleNode>,
+    options: Option<DiffOptions>,
+) -> napi::Result<MerkleDiff> {
+    trace_event(format!("diff {} against {} nodes", old_nodes.len(), new_nodes.len()));
+    diff_nodes(&old_nodes, &new_nodes, options)
+}
 
-    let new_files: BTreeMap<String, String> = new_nodes
-        .iter()
-        .filter(|n| n.is_file)
-        .map(|n| (n.path.clone(), n.hash.clone()))
-        .collect();
+/// `diff_merkle_trees` over borrowed nodes
+pub(crate) fn diff_nodes(
+    old_nodes: &[MerkleNode],
+    new_nodes: &[MerkleNode],
+    options: Option<DiffOptions>,
+) -> napi::Result<MerkleDiff> {
+    check_comparable(old_nodes, new_nodes)?;
+
+    let options = options.unwrap_or_default();
+    let detect_renames = options.detect_renames == Some(true);
+    let include_directories = options.include_directories == Some(true);
+
+    if roots_prove_equal(old_nodes, new_nodes)? {
+        return Ok(MerkleDiff {
+            added: vec![],
+            removed: vec![],
+            modified: vec![],
+            changed_directories: include_directories.then(Vec::new),
+            renamed: detect_renames.then(Vec::new),
+        });
+    }
+
+    let old_files = file_hashes(old_nodes);
+    let new_files = file_hashes(new_nodes);
 
     let mut added = Vec::new();
     let mut removed = Vec::new();
@@ -150,33 +354,1492 @@ pub fn diff_merkle_trees(old_nodes: Vec<MerkleNode>, new_nodes: Vec<MerkleNode>)
         }
     }
 
-    MerkleDiff {
+    let renamed = detect_renames
+        .then(|| pair_renames(&mut removed, &mut added, &old_files, &new_files));
+
+    let changed_directories =
+        include_directories.then(|| changed_directories(old_nodes, new_nodes));
+
+    Ok(MerkleDiff {
         added,
         removed,
         modified,
+        changed_directories,
+        renamed,
+    })
+}
+
+/// A changed file with its hashes on either side
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct DiffEntry {
+    pub path: String,
+    /// Hash in the old tree (absent for added files)
+    pub old_hash: Option<String>,
+    /// Hash in the new tree (absent for removed files)
+    pub new_hash: Option<String>,
+}
+
+/// Result of `diff_merkle_trees_detailed`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct DetailedMerkleDiff {
+    pub added: Vec<DiffEntry>,
+    pub removed: Vec<DiffEntry>,
+    pub modified: Vec<DiffEntry>,
+    pub changed_directories: Option<Vec<String>>,
+    pub renamed: Option<Vec<FileRename>>,
+}
+
+/// `diff_merkle_trees` with the old and new hash of every changed file, so callers
+/// can decide what to fetch or invalidate without looking the hashes up again
+#[napi(catch_unwind)]
+pub fn diff_merkle_trees_detailed(
+    old_nodes: Vec<MerkleNode>,
+    new_nodes: Vec<MerkleNode>,
+    options: Option<DiffOptions>,
+) -> napi::Result<DetailedMerkleDiff> {
+    let old_files = file_hashes(&old_nodes);
+    let new_files = file_hashes(&new_nodes);
+    let diff = diff_merkle_trees(old_nodes, new_nodes, options)?;
+    let detail = |paths: Vec<String>| -> Vec<DiffEntry> {
+        paths
+            .into_iter()
+            .map(|path| DiffEntry {
+                old_hash: old_files.get(&path).cloned(),
+                new_hash: new_files.get(&path).cloned(),
+                path,
+            })
+            .collect()
+    };
+    Ok(DetailedMerkleDiff {
+        added: detail(diff.added),
+        removed: detail(diff.removed),
+        modified: detail(diff.modified),
+        changed_directories: diff.changed_directories,
+        renamed: diff.renamed,
+    })
+}
+
+/// How a file differs between two trees
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum ChangeKind {
+    #[default]
+    Added,
+    Removed,
+    Modified,
+}
+
+/// One changed file reported by `diff_merkle_trees_streaming`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct DiffEvent {
+    pub path: String,
+    pub kind: ChangeKind,
+    /// Hash in the old tree (absent for added files)
+    pub old_hash: Option<String>,
+    /// Hash in the new tree (absent for removed files)
+    pub new_hash: Option<String>,
+}
+
+/// Options for `diff_merkle_trees_streaming`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct StreamingDiffOptions {
+    /// Changes per callback invocation (default 1000)
+    pub batch_size: Option<u32>,
+}
+
+/// Result of `diff_merkle_trees_streaming`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct StreamingDiffSummary {
+    /// Changes passed to the callback
+    pub emitted: u32,
+    /// False when the callback stopped the diff early
+    pub completed: bool,
+}
+
+const DEFAULT_DIFF_BATCH_SIZE: u32 = 1000;
+
+/// Diff two trees without building the result in memory: changed files are
+/// passed to `callback` in path order, in arrays of up to `batch_size` events.
+/// Returning `false` from the callback stops the diff. Renames and directories
+/// are not reported; use `diff_merkle_trees` for those.
+#[napi(catch_unwind)]
+pub fn diff_merkle_trees_streaming(
+    old_nodes: Vec<MerkleNode>,
+    new_nodes: Vec<MerkleNode>,
+    callback: JsFunction,
+    options: Option<StreamingDiffOptions>,
+) -> napi::Result<StreamingDiffSummary> {
+    check_comparable(&old_nodes, &new_nodes)?;
+    let batch_size = options
+        .unwrap_or_default()
+        .batch_size
+        .unwrap_or(DEFAULT_DIFF_BATCH_SIZE)
+        .max(1) as usize;
+
+    let mut summary = StreamingDiffSummary {
+        emitted: 0,
+        completed: true,
+    };
+    if roots_prove_equal(&old_nodes, &new_nodes)? {
+        return Ok(summary);
+    }
+
+    let old_files = sorted_files(&old_nodes);
+    let new_files = sorted_files(&new_nodes);
+
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut flush = |batch: &mut Vec<DiffEvent>| -> napi::Result<bool> {
+        summary.emitted += batch.len() as u32;
+        let result: JsUnknown = callback.call1(std::mem::take(batch))?;
+        let stop = result.get_type()? == ValueType::Boolean
+            && !result.coerce_to_bool()?.get_value()?;
+        Ok(!stop)
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_files.len() || j < new_files.len() {
+        let old = old_files.get(i);
+        let new = new_files.get(j);
+        let event = match (old, new) {
+            (Some(&(old_path, old_hash)), Some(&(new_path, new_hash))) if old_path == new_path => {
+                i += 1;
+                j += 1;
+                if old_hash == new_hash {
+                    continue;
+                }
+                DiffEvent {
+                    path: new_path.to_string(),
+                    kind: ChangeKind::Modified,
+                    old_hash: Some(old_hash.to_string()),
+                    new_hash: Some(new_hash.to_string()),
+                }
+            }
+            (Some(&(old_path, old_hash)), new) if new.is_none_or(|&(p, _)| old_path < p) => {
+                i += 1;
+                DiffEvent {
+                    path: old_path.to_string(),
+                    kind: ChangeKind::Removed,
+                    old_hash: Some(old_hash.to_string()),
+                    new_hash: None,
+                }
+            }
+            (_, Some(&(new_path, new_hash))) => {
+                j += 1;
+                DiffEvent {
+                    path: new_path.to_string(),
+                    kind: ChangeKind::Added,
+                    old_hash: None,
+                    new_hash: Some(new_hash.to_string()),
+                }
+            }
+            (_, None) => break,
+        };
+        batch.push(event);
+        if batch.len() == batch_size && !flush(&mut batch)? {
+            summary.completed = false;
+            return Ok(summary);
+        }
+    }
+    if !batch.is_empty() && !flush(&mut batch)? {
+        summary.completed = false;
     }
+    Ok(summary)
 }
 
-/// Get root hash from a list of Merkle nodes
-#[napi]
-pub fn get_root_hash(nodes: Vec<MerkleNode>) -> Option<String> {
-    // Root is the node with the shortest path (or "." or "")
+/// How a file changed relative to the common base of a three-way diff
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, PartialEq, Eq)]
+pub enum MergeStatus {
+    /// Changed locally only; take the local version
+    LocalOnly,
+    /// Changed remotely only; take the remote version
+    RemoteOnly,
+    /// Changed on both sides in the same way; nothing to resolve
+    Converged,
+    /// Changed on both sides differently (including modify vs delete)
+    Conflict,
+}
+
+/// A file that changed on at least one side; absent hashes mean the file does
+/// not exist on that side
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct MergeEntry {
+    pub path: String,
+    pub status: MergeStatus,
+    pub base_hash: Option<String>,
+    pub local_hash: Option<String>,
+    pub remote_hash: Option<String>,
+}
+
+/// Result of `diff3`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct ThreeWayDiff {
+    /// Changed files, sorted by path
+    pub entries: Vec<MergeEntry>,
+    /// Files identical on all three sides
+    pub unchanged: u32,
+}
+
+/// Three-way diff of the files of a local and a remote tree against their
+/// common base, for deterministic conflict handling during sync. A file counts
+/// as changed on a side when it was added, removed or modified there. Fails
+/// unless all trees share algorithm, hash scheme and namespace.
+#[napi(catch_unwind)]
+pub fn diff3(
+    base_nodes: Vec<MerkleNode>,
+    local_nodes: Vec<MerkleNode>,
+    remote_nodes: Vec<MerkleNode>,
+) -> napi::Result<ThreeWayDiff> {
+    check_comparable(&base_nodes, &local_nodes)?;
+    check_comparable(&base_nodes, &remote_nodes)?;
+    check_comparable(&local_nodes, &remote_nodes)?;
+
+    let base = file_hashes(&base_nodes);
+    let local = file_hashes(&local_nodes);
+    let remote = file_hashes(&remote_nodes);
+    let paths: BTreeSet<&String> = base.keys().chain(local.keys()).chain(remote.keys()).collect();
+
+    let mut entries = Vec::new();
+    let mut unchanged = 0;
+    for path in paths {
+        let base_hash = base.get(path);
+        let local_hash = local.get(path);
+        let remote_hash = remote.get(path);
+        let status = match (local_hash != base_hash, remote_hash != base_hash) {
+            (false, false) => {
+                unchanged += 1;
+                continue;
+            }
+            (true, false) => MergeStatus::LocalOnly,
+            (false, true) => MergeStatus::RemoteOnly,
+            (true, true) if local_hash == remote_hash => MergeStatus::Converged,
+            (true, true) => MergeStatus::Conflict,
+        };
+        entries.push(MergeEntry {
+            path: path.clone(),
+            status,
+            base_hash: base_hash.cloned(),
+            local_hash: local_hash.cloned(),
+            remote_hash: remote_hash.cloned(),
+        });
+    }
+    Ok(ThreeWayDiff { entries, unchanged })
+}
+
+/// (path, hash) of the file nodes, sorted by path
+fn sorted_files(nodes: &[MerkleNode]) -> Vec<(&str, &str)> {
+    let mut files: Vec<(&str, &str)> = nodes
+        .iter()
+        .filter(|n| n.is_file)
+        .map(|n| (n.path.as_str(), n.hash.as_str()))
+        .collect();
+    files.sort_unstable();
+    files
+}
+
+/// Fail unless both trees use the same algorithm, hash scheme and namespace
+/// (an empty tree is comparable with anything)
+fn check_comparable(old_nodes: &[MerkleNode], new_nodes: &[MerkleNode]) -> napi::Result<()> {
+    let old_algorithm = tree_algorithm(old_nodes)?;
+    let new_algorithm = tree_algorithm(new_nodes)?;
+    if !old_nodes.is_empty() && !new_nodes.is_empty() && old_algorithm != new_algorithm {
+        return Err(napi::Error::from_reason(format!(
+            "Cannot diff trees hashed with different algorithms ({:?} vs {:?})",
+            old_algorithm, new_algorithm
+        )));
+    }
+    let old_scheme = tree_scheme(old_nodes)?;
+    let new_scheme = tree_scheme(new_nodes)?;
+    if !old_nodes.is_empty() && !new_nodes.is_empty() && old_scheme != new_scheme {
+        return Err(napi::Error::from_reason(format!(
+            "Cannot diff trees with different hash schemes ({} vs {})",
+            old_scheme, new_scheme
+        )));
+    }
+    let old_namespace = tree_namespace(old_nodes)?;
+    let new_namespace = tree_namespace(new_nodes)?;
+    if !old_nodes.is_empty() && !new_nodes.is_empty() && old_namespace != new_namespace {
+        return Err(napi::Error::from_reason(format!(
+            "Cannot diff trees from different namespaces ({:?} vs {:?})",
+            old_namespace, new_namespace
+        )));
+    }
+    Ok(())
+}
+
+/// Path -> hash of the file (leaf) nodes
+fn file_hashes(nodes: &[MerkleNode]) -> BTreeMap<String, String> {
     nodes
         .iter()
-        .min_by_key(|n| n.path.len())
-        .map(|n| n.hash.clone())
+        .filter(|n| n.is_file)
+        .map(|n| (n.path.clone(), n.hash.clone()))
+        .collect()
+}
+
+/// Move removed/added pairs with equal hashes out of `removed` and `added`
+fn pair_renames(
+    removed: &mut Vec<String>,
+    added: &mut Vec<String>,
+    old_files: &BTreeMap<String, String>,
+    new_files: &BTreeMap<String, String>,
+) -> Vec<FileRename> {
+    let mut removed_by_hash: BTreeMap<&str, VecDeque<&String>> = BTreeMap::new();
+    for path in removed.iter() {
+        removed_by_hash
+            .entry(old_files[path].as_str())
+            .or_default()
+            .push_back(path);
+    }
+
+    let mut renamed = Vec::new();
+    for path in added.iter() {
+        if let Some(from) = removed_by_hash
+            .get_mut(new_files[path].as_str())
+            .and_then(|candidates| candidates.pop_front())
+        {
+            renamed.push(FileRename {
+                from: from.clone(),
+                to: path.clone(),
+                hash: new_files[path].clone(),
+            });
+        }
+    }
+
+    let moved_from: BTreeSet<&str> = renamed.iter().map(|r| r.from.as_str()).collect();
+    let moved_to: BTreeSet<&str> = renamed.iter().map(|r| r.to.as_str()).collect();
+    removed.retain(|p| !moved_from.contains(p.as_str()));
+    added.retain(|p| !moved_to.contains(p.as_str()));
+    renamed
+}
+
+fn changed_directories(old_nodes: &[MerkleNode], new_nodes: &[MerkleNode]) -> Vec<String> {
+    let directories = |nodes: &[MerkleNode]| -> BTreeMap<String, String> {
+        nodes
+            .iter()
+            .filter(|n| !n.is_file)
+            .map(|n| (n.path.clone(), n.hash.clone()))
+            .collect()
+    };
+    let old_dirs = directories(old_nodes);
+    let new_dirs = directories(new_nodes);
+    let mut changed: BTreeSet<String> = new_dirs
+        .iter()
+        .filter(|(path, hash)| old_dirs.get(*path) != Some(*hash))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(old_dirs.into_keys().filter(|path| !new_dirs.contains_key(path)));
+    changed.into_iter().collect()
+}
+
+/// A file change for `apply_changes`
+#[napi(object)]
+#[derive(Clone)]
+pub struct TreeChange {
+    /// File path relative to the tree root
+    pub path: String,
+    /// New content hash (required unless `deleted` or `is_directory`)
+    pub hash: Option<String>,
+    /// Remove the file from the tree
+    pub deleted: Option<bool>,
+    /// Declares `path` as a directory, like a `FileHashEntry` directory entry:
+    /// it is created if missing and kept even when this update empties it
+    pub is_directory: Option<bool>,
+    /// New file size in bytes, for the directories' aggregates
+    pub size: Option<i64>,
+}
+
+/// Result of `apply_changes`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct UpdatedTree {
+    /// Every node, in no particular order
+    pub nodes: Vec<MerkleNode>,
+    /// Hash of the root node; absent once the tree is empty
+    pub root_hash: Option<String>,
+}
+
+/// Apply file additions, modifications and deletions to a tree built by
+/// `build_merkle_tree`, rehashing only the changed files' ancestor directories.
+/// Directories emptied by a deletion are removed unless `changes` declares them
+/// (`is_directory`), so the result matches a full rebuild from the new files
+/// plus the already empty and the declared directories.
+/// Change paths are normalized like in `build_merkle_tree`.
+#[napi(catch_unwind)]
+pub fn apply_changes(
+    nodes: Vec<MerkleNode>,
+    changes: Vec<TreeChange>,
+) -> napi::Result<UpdatedTree> {
+    let algorithm = tree_algorithm(&nodes)?;
+    let scheme = tree_scheme(&nodes)?;
+    let namespace = tree_namespace(&nodes)?;
+    let mut tree = NodeIndex::new(nodes);
+    tree.apply(changes, algorithm, scheme, &namespace)?;
+    Ok(UpdatedTree {
+        root_hash: tree.get(".").map(|n| n.hash.clone()),
+        nodes: tree.into_nodes(),
+    })
+}
+
+/// The nodes of a tree with an index by path, so an update touches only the
+/// changed paths and their ancestors. Removals do not preserve node order.
+pub(crate) struct NodeIndex {
+    nodes: Vec<MerkleNode>,
+    positions: HashMap<String, usize>,
+    /// While `apply` runs: each touched path with its node from before, to
+    /// restore when a change is rejected
+    journal: Option<HashMap<String, Option<MerkleNode>>>,
+}
+
+impl NodeIndex {
+    /// Index `nodes`; of several nodes with the same path the first is kept
+    pub(crate) fn new(nodes: Vec<MerkleNode>) -> Self {
+        let mut tree = NodeIndex {
+            nodes: Vec::with_capacity(nodes.len()),
+            positions: HashMap::with_capacity(nodes.len()),
+            journal: None,
+        };
+        for node in nodes {
+            if !tree.positions.contains_key(&node.path) {
+                tree.positions.insert(node.path.clone(), tree.nodes.len());
+                tree.nodes.push(node);
+            }
+        }
+        tree
+    }
+
+    pub(crate) fn into_nodes(self) -> Vec<MerkleNode> {
+        self.nodes
+    }
+
+    /// Every node, in no particular order
+    pub(crate) fn nodes(&self) -> &[MerkleNode] {
+        &self.nodes
+    }
+
+    pub(crate) fn get(&self, path: &str) -> Option<&MerkleNode> {
+        self.positions.get(path).map(|&i| &self.nodes[i])
+    }
+
+    fn get_mut(&mut self, path: &str) -> Option<&mut MerkleNode> {
+        self.record(path);
+        self.positions.get(path).map(|&i| &mut self.nodes[i])
+    }
+
+    /// Journal the node at `path` before its first change
+    fn record(&mut self, path: &str) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        if !journal.contains_key(path) {
+            let node = self.positions.get(path).map(|&i| self.nodes[i].clone());
+            journal.insert(path.to_string(), node);
+        }
+    }
+
+    fn insert(&mut self, node: MerkleNode) {
+        self.record(&node.path);
+        match self.positions.get(&node.path) {
+            Some(&i) => self.nodes[i] = node,
+            None => {
+                self.positions.insert(node.path.clone(), self.nodes.len());
+                self.nodes.push(node);
+            }
+        }
+    }
+
+    fn remove(&mut self, path: &str) -> Option<MerkleNode> {
+        self.record(path);
+        let i = self.positions.remove(path)?;
+        let node = self.nodes.swap_remove(i);
+        if let Some(moved) = self.nodes.get(i) {
+            if let Some(position) = self.positions.get_mut(&moved.path) {
+                *position = i;
+            }
+        }
+        Some(node)
+    }
+
+    /// Apply `changes` as described for `apply_changes` to a tree hashed with
+    /// `algorithm`, `scheme` and `namespace`. When a change is rejected the
+    /// nodes are restored as they were, though not necessarily in their order.
+    pub(crate) fn apply(
+        &mut self,
+        changes: Vec<TreeChange>,
+        algorithm: HashAlgorithm,
+        scheme: u32,
+        namespace: &Option<String>,
+    ) -> napi::Result<()> {
+        self.journal = Some(HashMap::new());
+        let result = self.apply_journaled(changes, algorithm, scheme, namespace);
+        let journal = self.journal.take().unwrap_or_default();
+        if result.is_err() {
+            for (path, node) in journal {
+                match node {
+                    Some(node) => self.insert(node),
+                    None => {
+                        self.remove(&path);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn apply_journaled(
+        &mut self,
+        changes: Vec<TreeChange>,
+        algorithm: HashAlgorithm,
+        scheme: u32,
+        namespace: &Option<String>,
+    ) -> napi::Result<()> {
+        let mut dirty: HashSet<String> = HashSet::new();
+        let mut declared: HashSet<String> = HashSet::new();
+
+        for change in changes {
+            let path = normalize_tree_path(&change.path);
+            if change.is_directory == Some(true) {
+                if change.deleted == Some(true) {
+                    return Err(napi::Error::from_reason(format!(
+                        "Cannot delete directory {}; delete the files below it",
+                        path
+                    )));
+                }
+                let dir = if path.is_empty() { ".".to_string() } else { path };
+                if self.get(&dir).is_some_and(|n| n.is_file) {
+                    return Err(napi::Error::from_reason(format!(
+                        "Cannot declare file {} as a directory",
+                        dir
+                    )));
+                }
+                if dir != "." {
+                    self.link_to_parents(&dir, algorithm, scheme, namespace)?;
+                }
+                if self.get(&dir).is_none() {
+                    self.insert(directory_node(dir.clone(), algorithm, scheme, namespace));
+                }
+                mark_ancestors(&mut dirty, &dir);
+                dirty.insert(dir.clone());
+                declared.insert(dir);
+                continue;
+            }
+
+            if path == "." || self.get(&path).is_some_and(|n| !n.is_file) {
+                return Err(napi::Error::from_reason(format!(
+                    "Cannot apply a file change to directory {}",
+                    path
+                )));
+            }
+            if change.deleted == Some(true) {
+                if self.remove(&path).is_some() {
+                    self.unlink_from_parents(&path, &declared);
+                }
+            } else {
+                let hash = change.hash.ok_or_else(|| {
+                    napi::Error::from_reason(format!("Change for {} has no hash", path))
+                })?;
+                self.link_to_parents(&path, algorithm, scheme, namespace)?;
+                self.insert(MerkleNode {
+                    path: path.clone(),
+                    hash: leaf_hash(hash, algorithm, namespace.as_deref()),
+                    is_file: true,
+                    children: vec![],
+                    algorithm: Some(algorithm),
+                    is_root: None,
+                    size: change.size,
+                    file_count: Some(1),
+                    hash_scheme: Some(scheme),
+                    namespace: namespace.clone(),
+                });
+            }
+            mark_ancestors(&mut dirty, &path);
+        }
+
+        // Recompute dirty directories bottom-up; removed ones are skipped
+        let mut dirty: Vec<String> = dirty.into_iter().filter(|d| self.get(d).is_some()).collect();
+        dirty.sort_by_key(|path| std::cmp::Reverse(dir_depth(path)));
+        for dir in dirty {
+            let children: Vec<&MerkleNode> = self.nodes[self.positions[&dir]]
+                .children
+                .iter()
+                .filter_map(|child| self.get(child))
+                .collect();
+            let entries = children.iter().map(|node| DirEntry::of(node)).collect();
+            let hash = directory_hash(entries, algorithm, scheme, namespace.as_deref());
+            let (size, file_count) = aggregate(children.into_iter());
+            if let Some(node) = self.get_mut(&dir) {
+                node.hash = hash;
+                node.size = size;
+                node.file_count = Some(file_count);
+            }
+        }
+        Ok(())
+    }
+
+    /// Register `path` under its parent, creating missing ancestor directories
+    fn link_to_parents(
+        &mut self,
+        path: &str,
+        algorithm: HashAlgorithm,
+        scheme: u32,
+        namespace: &Option<String>,
+    ) -> napi::Result<()> {
+        let mut child = path.to_string();
+        loop {
+            let dir = parent_path(&child);
+            if self.get(&dir).is_none() {
+                self.insert(directory_node(dir.clone(), algorithm, scheme, namespace));
+            }
+            let Some(node) = self.get_mut(&dir) else {
+                return Ok(());
+            };
+            if node.is_file {
+                return Err(napi::Error::from_reason(format!(
+                    "Cannot add {} below file {}",
+                    path, dir
+                )));
+            }
+            if !node.children.contains(&child) {
+                node.children.push(child);
+            }
+            if dir == "." {
+                return Ok(());
+            }
+            child = dir;
+        }
+    }
+
+    /// Detach a removed `path` from its parent, pruning directories left empty
+    /// unless they are `declared`
+    fn unlink_from_parents(&mut self, path: &str, declared: &HashSet<String>) {
+        let mut child = path.to_string();
+        loop {
+            let dir = parent_path(&child);
+            let Some(node) = self.get_mut(&dir) else {
+                return;
+            };
+            node.children.retain(|c| c != &child);
+            if !node.children.is_empty() || declared.contains(&dir) {
+                return;
+            }
+            self.remove(&dir);
+            if dir == "." {
+                return;
+            }
+            child = dir;
+        }
+    }
+}
+
+/// A directory node without children; its hash is computed by the caller
+fn directory_node(
+    path: String,
+    algorithm: HashAlgorithm,
+    scheme: u32,
+    namespace: &Option<String>,
+) -> MerkleNode {
+    MerkleNode {
+        is_root: (path == ".").then_some(true),
+        path,
+        hash: String::new(),
+        is_file: false,
+        children: vec![],
+        algorithm: Some(algorithm),
+        size: None,
+        file_count: None,
+        hash_scheme: Some(scheme),
+        namespace: namespace.clone(),
+    }
+}
+
+/// Add every ancestor directory of `path` to `dirty`
+fn mark_ancestors(dirty: &mut HashSet<String>, path: &str) {
+    let mut dir = parent_path(path);
+    while dir != "." {
+        let parent = parent_path(&dir);
+        dirty.insert(dir);
+        dir = parent;
+    }
+    dirty.insert(dir);
+}
+
+/// One level of a Merkle proof
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct MerkleProofStep {
+    /// Directory whose hash this step reproduces
+    pub directory: String,
+    /// Hashes of the directory's other children
+    pub sibling_hashes: Vec<String>,
+    /// The other children with names and kinds, needed for hash scheme 2
+    pub siblings: Option<Vec<ProofSibling>>,
+}
+
+/// A directory child other than the one being proven
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct ProofSibling {
+    /// Last path component
+    pub name: String,
+    pub hash: String,
+    pub is_file: bool,
+}
+
+/// Sibling-hash chain from a file up to the root
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct MerkleProof {
+    pub algorithm: HashAlgorithm,
+    /// Directory hash scheme of the tree; absent means 1
+    pub hash_scheme: Option<u32>,
+    /// Namespace of the tree, if any
+    pub namespace: Option<String>,
+    /// From the file's parent directory up to the root
+    pub steps: Vec<MerkleProofStep>,
+}
+
+/// Proof that the file at `path` is part of the tree, checkable against the root
+/// hash alone with `verify_merkle_proof`
+#[napi(catch_unwind)]
+pub fn get_merkle_proof(nodes: Vec<MerkleNode>, path: String) -> napi::Result<MerkleProof> {
+    let algorithm = tree_algorithm(&nodes)?;
+    let scheme = tree_scheme(&nodes)?;
+    let namespace = tree_namespace(&nodes)?;
+    let tree: BTreeMap<&str, &MerkleNode> = nodes.iter().map(|n| (n.path.as_str(), n)).collect();
+    if !tree.get(path.as_str()).is_some_and(|n| n.is_file) {
+        return Err(napi::Error::from_reason(format!("No file {} in tree", path)));
+    }
+
+    let mut steps = Vec::new();
+    let mut child = path;
+    loop {
+        let directory = parent_path(&child);
+        let node = tree.get(directory.as_str()).ok_or_else(|| {
+            napi::Error::from_reason(format!("Tree is missing directory {}", directory))
+        })?;
+        let siblings: Vec<&MerkleNode> = node
+            .children
+            .iter()
+            .filter(|c| **c != child)
+            .filter_map(|c| tree.get(c.as_str()).copied())
+            .collect();
+        let named_siblings = (scheme != LEGACY_HASH_SCHEME).then(|| {
+            siblings
+                .iter()
+                .map(|n| ProofSibling {
+                    name: base_name(&n.path).to_string(),
+                    hash: n.hash.clone(),
+                    is_file: n.is_file,
+                })
+                .collect()
+        });
+        steps.push(MerkleProofStep {
+            directory: directory.clone(),
+            sibling_hashes: siblings.iter().map(|n| n.hash.clone()).collect(),
+            siblings: named_siblings,
+        });
+        if directory == "." {
+            return Ok(MerkleProof {
+                algorithm,
+                hash_scheme: Some(scheme),
+                namespace,
+                steps,
+            });
+        }
+        child = directory;
+    }
+}
+
+/// Check that `file_hash` at `path` folds up to `root_hash` through `proof`.
+/// With hash scheme 1 directory hashes cover child hashes but not names, so this
+/// proves the content is a leaf at that depth under the root; scheme 2 also
+/// proves the names along `path`. The steps must follow `path`'s ancestors.
+/// `file_hash` is the content hash; it is salted with the proof's namespace.
+#[napi(catch_unwind)]
+pub fn verify_merkle_proof(
+    root_hash: String,
+    path: String,
+    file_hash: String,
+    proof: MerkleProof,
+) -> bool {
+    let scheme = proof.hash_scheme.unwrap_or(LEGACY_HASH_SCHEME);
+    if check_scheme(scheme).is_err() {
+        return false;
+    }
+    let mut expected_directory = path;
+    let mut current = leaf_hash(file_hash, proof.algorithm, proof.namespace.as_deref());
+    let mut is_file = true;
+    for step in proof.steps {
+        let child = expected_directory;
+        expected_directory = parent_path(&child);
+        if step.directory != expected_directory {
+            return false;
+        }
+        let mut entries: Vec<DirEntry> = if scheme == LEGACY_HASH_SCHEME {
+            step.sibling_hashes
+                .iter()
+                .map(|hash| DirEntry {
+                    name: "",
+                    hash,
+                    is_file: true,
+                })
+                .collect()
+        } else {
+            let Some(siblings) = &step.siblings else {
+                return false;
+            };
+            siblings
+                .iter()
+                .map(|s| DirEntry {
+                    name: &s.name,
+                    hash: &s.hash,
+                    is_file: s.is_file,
+                })
+                .collect()
+        };
+        entries.push(DirEntry {
+            name: base_name(&child),
+            hash: &current,
+            is_file,
+        });
+        current = directory_hash(entries, proof.algorithm, scheme, proof.namespace.as_deref());
+        is_file = false;
+    }
+    expected_directory == "." && current == root_hash
+}
+
+/// Get root hash from a list of Merkle nodes: the node marked `is_root`, else
+/// the node at "." (or "" in old trees). None when there is no root.
+#[napi(catch_unwind)]
+pub fn get_root_hash(nodes: Vec<MerkleNode>) -> Option<String> {
+    root_node(&nodes).map(|n| n.hash.clone())
+}
+
+/// Result of `get_tree_stats`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct TreeStats {
+    pub file_count: u32,
+    /// Directories including the root
+    pub directory_count: u32,
+    /// Depth of the deepest file; top-level files have depth 1
+    pub max_depth: u32,
+    /// Sum of file sizes, when every file has one
+    pub total_bytes: Option<i64>,
+    pub algorithm: HashAlgorithm,
+    pub hash_scheme: u32,
+    pub namespace: Option<String>,
+}
+
+/// Summarize a tree, e.g. for telemetry or to check that two trees about to be
+/// diffed describe comparable workspaces. Fails on mixed algorithms, schemes or
+/// namespaces.
+#[napi(catch_unwind)]
+pub fn get_tree_stats(nodes: Vec<MerkleNode>) -> napi::Result<TreeStats> {
+    let algorithm = tree_algorithm(&nodes)?;
+    let hash_scheme = tree_scheme(&nodes)?;
+    let namespace = tree_namespace(&nodes)?;
+    let files = nodes.iter().filter(|n| n.is_file);
+    let total_bytes = files.clone().map(|n| n.size).sum();
+    Ok(TreeStats {
+        file_count: files.clone().count() as u32,
+        directory_count: nodes.iter().filter(|n| !n.is_file).count() as u32,
+        max_depth: files.map(|n| dir_depth(&n.path) as u32).max().unwrap_or(0),
+        total_bytes,
+        algorithm,
+        hash_scheme,
+        namespace,
+    })
+}
+
+/// The part of a tree below directory `prefix` as a standalone tree rooted at ".",
+/// with hashes unchanged, e.g. to diff or sync one package of a monorepo.
+/// Fails when `prefix` is not a directory of the tree.
+#[napi(catch_unwind)]
+pub fn extract_subtree(nodes: Vec<MerkleNode>, prefix: String) -> napi::Result<Vec<MerkleNode>> {
+    let prefix = normalize_tree_path(&prefix);
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() || prefix == "." {
+        return Ok(nodes);
+    }
+    match nodes.iter().find(|n| n.path == prefix) {
+        Some(node) if !node.is_file => {}
+        Some(_) => {
+            return Err(napi::Error::from_reason(format!("{} is a file, not a directory", prefix)))
+        }
+        None => return Err(napi::Error::from_reason(format!("{} is not in the tree", prefix))),
+    }
+
+    let relative = |path: &str| -> Option<String> {
+        if path == prefix {
+            Some(".".to_string())
+        } else {
+            path.strip_prefix(prefix)?.strip_prefix('/').map(str::to_string)
+        }
+    };
+    Ok(nodes
+        .into_iter()
+        .filter_map(|node| {
+            let path = relative(&node.path)?;
+            Some(MerkleNode {
+                children: node.children.iter().filter_map(|c| relative(c)).collect(),
+                is_root: (path == ".").then_some(true),
+                path,
+                ..node
+            })
+        })
+        .collect())
+}
+
+/// One workspace folder for `merge_trees`
+#[napi(object)]
+#[derive(Clone)]
+pub struct NamedTree {
+    /// Top-level directory name the tree is mounted under; must not contain '/'
+    pub name: String,
+    pub nodes: Vec<MerkleNode>,
+}
+
+/// Combine the trees of a multi-root workspace under a synthetic super-root.
+/// Each tree's paths are prefixed with `name/` and its root becomes the directory
+/// `name`; the new root at "." hashes over those directories, so the result
+/// equals `build_merkle_tree` over the prefixed file paths. Empty trees are skipped.
+/// Nodes come back sorted by path, and neither they nor the root hash depend on
+/// the order of `roots`.
+#[napi(catch_unwind)]
+pub fn merge_trees(roots: Vec<NamedTree>) -> napi::Result<Vec<MerkleNode>> {
+    let mut algorithm = None;
+    let mut scheme = None;
+    let mut namespace = None;
+    let mut names = BTreeSet::new();
+    let mut combined = Vec::new();
+    let mut root_entries = Vec::new();
+
+    for tree in roots {
+        let name = tree.name;
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(napi::Error::from_reason(format!("Invalid root name {:?}", name)));
+        }
+        if !names.insert(name.clone()) {
+            return Err(napi::Error::from_reason(format!("Duplicate root name {}", name)));
+        }
+        if tree.nodes.is_empty() {
+            continue;
+        }
+        let tree_algorithm = tree_algorithm(&tree.nodes)?;
+        let expected = *algorithm.get_or_insert(tree_algorithm);
+        if tree_algorithm != expected {
+            return Err(napi::Error::from_reason(format!(
+                "Root {} was hashed with {:?}, other roots with {:?}",
+                name, tree_algorithm, expected
+            )));
+        }
+        let tree_scheme = tree_scheme(&tree.nodes)?;
+        let expected = *scheme.get_or_insert(tree_scheme);
+        if tree_scheme != expected {
+            return Err(napi::Error::from_reason(format!(
+                "Root {} uses hash scheme {}, other roots {}",
+                name, tree_scheme, expected
+            )));
+        }
+        let tree_namespace = tree_namespace(&tree.nodes)?;
+        let expected = namespace.get_or_insert(tree_namespace.clone());
+        if tree_namespace != *expected {
+            return Err(napi::Error::from_reason(format!(
+                "Root {} is in namespace {:?}, other roots in {:?}",
+                name, tree_namespace, expected
+            )));
+        }
+
+        let root_path = root_node(&tree.nodes)
+            .map(|n| n.path.clone())
+            .ok_or_else(|| napi::Error::from_reason(format!("Root {} has no root node", name)))?;
+        let mount = |path: &str| {
+            if path == root_path {
+                name.clone()
+            } else {
+                format!("{}/{}", name, path)
+            }
+        };
+        for node in tree.nodes {
+            if node.path == root_path {
+                root_entries.push((name.clone(), node.hash.clone()));
+            }
+            combined.push(MerkleNode {
+                path: mount(&node.path),
+                children: node.children.iter().map(|c| mount(c)).collect(),
+                is_root: None,
+                ..node
+            });
+        }
+    }
+
+    let (Some(algorithm), Some(scheme), Some(namespace)) = (algorithm, scheme, namespace) else {
+        return Ok(vec![]);
+    };
+    let entries = root_entries
+        .iter()
+        .map(|(name, hash)| DirEntry {
+            name,
+            hash,
+            is_file: false,
+        })
+        .collect();
+    let mut children: Vec<String> = combined
+        .iter()
+        .filter(|n| !n.path.contains('/'))
+        .map(|n| n.path.clone())
+        .collect();
+    children.sort();
+    let (size, file_count) = aggregate(combined.iter().filter(|n| !n.path.contains('/')));
+    combined.push(MerkleNode {
+        path: ".".to_string(),
+        hash: directory_hash(entries, algorithm, scheme, namespace.as_deref()),
+        is_file: false,
+        children,
+        algorithm: Some(algorithm),
+        is_root: Some(true),
+        size,
+        file_count: Some(file_count),
+        hash_scheme: Some(scheme),
+        namespace,
+    });
+    combined.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(combined)
 }
 
 #[napi(object)]
 #[derive(Clone)]
 pub struct FileHashEntry {
     pub path: String,
+    /// Content hash; ignored for directory entries
     pub hash: String,
+    /// Declares a directory, so it appears in the tree even when empty (or when
+    /// everything in it is ignored)
+    pub is_directory: Option<bool>,
+    /// File size in bytes, summed into the directories' `size`
+    pub size: Option<i64>,
+}
+
+/// Total size (None if no child has one) and file count of a directory's children
+fn aggregate<'a>(children: impl Iterator<Item = &'a MerkleNode>) -> (Option<i64>, u32) {
+    let mut size = None;
+    let mut file_count = 0;
+    for child in children {
+        if let Some(child_size) = child.size {
+            *size.get_or_insert(0) += child_size;
+        }
+        file_count += child.file_count.unwrap_or(u32::from(child.is_file));
+    }
+    (size, file_count)
+}
+
+/// The node marked `is_root`, else the node at "." (or "" in old trees)
+pub(crate) fn root_node(nodes: &[MerkleNode]) -> Option<&MerkleNode> {
+    nodes
+        .iter()
+        .find(|n| n.is_root == Some(true))
+        .or_else(|| nodes.iter().find(|n| n.path == "."))
+        .or_else(|| nodes.iter().find(|n| n.path.is_empty()))
+}
+
+/// Whether equal root hashes alone show the trees are equal, so walking the
+/// files can be skipped. Only scheme 2 hashes child names: under scheme 1 a
+/// renamed or moved file leaves every directory hash unchanged.
+fn roots_prove_equal(old_nodes: &[MerkleNode], new_nodes: &[MerkleNode]) -> napi::Result<bool> {
+    if tree_scheme(old_nodes)? != NAMED_HASH_SCHEME
+        || tree_scheme(new_nodes)? != NAMED_HASH_SCHEME
+    {
+        return Ok(false);
+    }
+    Ok(match (root_node(old_nodes), root_node(new_nodes)) {
+        (Some(old_root), Some(new_root)) => old_root.hash == new_root.hash,
+        _ => false,
+    })
+}
+
+/// The single algorithm a tree was hashed with
+pub(crate) fn tree_algorithm(nodes: &[MerkleNode]) -> napi::Result<HashAlgorithm> {
+    let mut algorithms = nodes.iter().map(|n| n.algorithm.unwrap_or_default());
+    let first = algorithms.next().unwrap_or_default();
+    if algorithms.any(|a| a != first) {
+        return Err(napi::Error::from_reason(
+            "Tree mixes nodes hashed with different algorithms",
+        ));
+    }
+    Ok(first)
+}
+
+/// Scheme 1: directory hash over the sorted child hashes only (the default)
+pub const LEGACY_HASH_SCHEME: u32 = 1;
+/// Scheme 2: domain-separated directory hash over tagged, named children
+pub const NAMED_HASH_SCHEME: u32 = 2;
+
+/// Digested in place of the (empty) child list for empty directories under
+/// scheme 1, which would otherwise hash like an empty file
+const EMPTY_DIRECTORY_SENTINEL: &[u8] = b"\0empty-directory";
+
+/// Prefixes the namespace in namespaced hashes; no unsalted preimage starts with it
+const NAMESPACE_TAG: &[u8] = b"\0namespace";
+
+/// Follows the namespace in namespaced file hashes; no directory preimage
+/// continues with it
+const LEAF_TAG: &[u8] = b"\0leaf";
+
+/// Node type tags of hash scheme 2
+const TAG_FILE: u8 = 0;
+const TAG_DIRECTORY: u8 = 1;
+
+/// The single hash scheme a tree was built with
+pub(crate) fn tree_scheme(nodes: &[MerkleNode]) -> napi::Result<u32> {
+    let mut schemes = nodes.iter().map(|n| n.hash_scheme.unwrap_or(LEGACY_HASH_SCHEME));
+    let first = schemes.next().unwrap_or(LEGACY_HASH_SCHEME);
+    if schemes.any(|s| s != first) {
+        return Err(napi::Error::from_reason(
+            "Tree mixes nodes built with different hash schemes",
+        ));
+    }
+    check_scheme(first)
+}
+
+/// The single namespace a tree was built in
+pub(crate) fn tree_namespace(nodes: &[MerkleNode]) -> napi::Result<Option<String>> {
+    let mut namespaces = nodes.iter().map(|n| n.namespace.as_deref().filter(|n| !n.is_empty()));
+    let first = namespaces.next().flatten();
+    if namespaces.any(|n| n != first) {
+        return Err(napi::Error::from_reason(
+            "Tree mixes nodes from different namespaces",
+        ));
+    }
+    Ok(first.map(str::to_string))
+}
+
+pub(crate) fn check_scheme(scheme: u32) -> napi::Result<u32> {
+    match scheme {
+        LEGACY_HASH_SCHEME | NAMED_HASH_SCHEME => Ok(scheme),
+        _ => Err(napi::Error::from_reason(format!(
+            "Unsupported hash scheme {}",
+            scheme
+        ))),
+    }
+}
+
+/// A directory child as it enters the directory's hash
+struct DirEntry<'a> {
+    name: &'a str,
+    hash: &'a str,
+    is_file: bool,
+}
+
+impl<'a> DirEntry<'a> {
+    fn of(node: &'a MerkleNode) -> Self {
+        DirEntry {
+            name: base_name(&node.path),
+            hash: &node.hash,
+            is_file: node.is_file,
+        }
+    }
+}
+
+/// Hash of a directory. Scheme 1 digests the children's hashes, sorted for
+/// determinism, or `EMPTY_DIRECTORY_SENTINEL` when there are none. Scheme 2 digests
+///
+/// ```text
+/// u8:scheme u8:TAG_DIRECTORY children sorted by name x {
+///     u8:tag varint:name_len name varint:hash_len hash
+/// }
+/// ```
+///
+/// so a file whose content hash equals a directory's hash still changes the parent.
+/// A namespace is digested first (see `namespace_prefix`).
+fn directory_hash(
+    mut entries: Vec<DirEntry>,
+    algorithm: HashAlgorithm,
+    scheme: u32,
+    namespace: Option<&str>,
+) -> String {
+    let mut digester = Digester::new(algorithm);
+    if let Some(namespace) = namespace {
+        digester.update(&namespace_prefix(namespace));
+    }
+    if scheme == LEGACY_HASH_SCHEME && entries.is_empty() {
+        digester.update(EMPTY_DIRECTORY_SENTINEL);
+    } else if scheme == LEGACY_HASH_SCHEME {
+        entries.sort_by(|a, b| a.hash.cmp(b.hash));
+        for entry in &entries {
+            digester.update(entry.hash.as_bytes());
+        }
+    } else {
+        entries.sort_by(|a, b| a.name.cmp(b.name));
+        let mut preimage = vec![scheme as u8, TAG_DIRECTORY];
+        for entry in &entries {
+            preimage.push(if entry.is_file { TAG_FILE } else { TAG_DIRECTORY });
+            push_varint(&mut preimage, entry.name.len() as u64);
+            preimage.extend_from_slice(entry.name.as_bytes());
+            push_varint(&mut preimage, entry.hash.len() as u64);
+            preimage.extend_from_slice(entry.hash.as_bytes());
+        }
+        digester.update(&preimage);
+    }
+    digester.finalize_hex()
+}
+
+/// Hash of a file node: the content hash itself, or in a namespace the digest of
+///
+/// ```text
+/// NAMESPACE_TAG varint:len namespace LEAF_TAG content_hash
+/// ```
+pub(crate) fn leaf_hash(
+    content_hash: String,
+    algorithm: HashAlgorithm,
+    namespace: Option<&str>,
+) -> String {
+    let Some(namespace) = namespace.filter(|n| !n.is_empty()) else {
+        return content_hash;
+    };
+    let mut digester = Digester::new(algorithm);
+    let mut preimage = namespace_prefix(namespace);
+    preimage.extend_from_slice(LEAF_TAG);
+    preimage.extend_from_slice(content_hash.as_bytes());
+    digester.update(&preimage);
+    digester.finalize_hex()
+}
+
+fn namespace_prefix(namespace: &str) -> Vec<u8> {
+    let mut prefix = NAMESPACE_TAG.to_vec();
+    push_varint(&mut prefix, namespace.len() as u64);
+    prefix.extend_from_slice(namespace.as_bytes());
+    prefix
+}
+
+/// Last component of a tree path
+fn base_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Depth of a directory node; the root "." sits above the top-level directories
+fn dir_depth(path: &str) -> usize {
+    if path == "." {
+        0
+    } else {
+        path.matches('/').count() + 1
+    }
+}
+
+/// Tree form of a relative path: `\` separators become `/` and leading `./`
+/// segments are dropped. Backslashes are therefore not supported inside names.
+pub(crate) fn normalize_tree_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut trimmed = path.as_str();
+    while let Some(rest) = trimmed.strip_prefix("./") {
+        trimmed = rest.trim_start_matches('/');
+    }
+    trimmed.to_string()
 }
 
-fn parent_path(path: &str) -> String {
+pub(crate) fn parent_path(path: &str) -> String {
     match path.rfind('/') {
         Some(idx) => path[..idx].to_string(),
         None => ".".to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(files: &[(&str, &str)], hash_scheme: u32) -> Vec<MerkleNode> {
+        let files = files
+            .iter()
+            .map(|(path, hash)| FileHashEntry {
+                path: path.to_string(),
+                hash: hash.to_string(),
+                is_directory: None,
+                size: None,
+            })
+            .collect();
+        let options = MerkleOptions {
+            hash_scheme: Some(hash_scheme),
+            ..Default::default()
+        };
+        build_merkle_tree(files, Some(options)).unwrap()
+    }
+
+    fn root_hash(nodes: &[MerkleNode]) -> String {
+        nodes.iter().find(|n| n.path == ".").unwrap().hash.clone()
+    }
+
+    /// Tree of `files` plus the explicitly declared directories `dirs`
+    fn tree_with_dirs(files: &[(&str, &str)], dirs: &[&str]) -> Vec<MerkleNode> {
+        let entry = |path: &str, hash: &str, is_directory| FileHashEntry {
+            path: path.to_string(),
+            hash: hash.to_string(),
+            is_directory,
+            size: Some(1),
+        };
+        let files = files
+            .iter()
+            .map(|(path, hash)| entry(path, hash, None))
+            .chain(dirs.iter().map(|dir| entry(dir, "", Some(true))))
+            .collect();
+        build_merkle_tree(files, None).unwrap()
+    }
+
+    fn change(path: &str, hash: Option<&str>) -> TreeChange {
+        TreeChange {
+            path: path.to_string(),
+            hash: hash.map(str::to_string),
+            deleted: hash.is_none().then_some(true),
+            is_directory: None,
+            size: Some(1),
+        }
+    }
+
+    type NodeSummary = (String, String, bool, Vec<String>, Option<i64>, Option<u32>);
+
+    fn summarize(nodes: Vec<MerkleNode>) -> Vec<NodeSummary> {
+        let mut summary: Vec<_> = nodes
+            .into_iter()
+            .map(|n| {
+                let mut children = n.children;
+                children.sort();
+                (n.path, n.hash, n.is_file, children, n.size, n.file_count)
+            })
+            .collect();
+        summary.sort();
+        summary
+    }
+
+    #[test]
+    fn root_hash_covers_nested_files() {
+        let old = tree(&[("a.txt", "h1"), ("src/lib/b.rs", "h2")], LEGACY_HASH_SCHEME);
+        let new = tree(&[("a.txt", "h1"), ("src/lib/b.rs", "h3")], LEGACY_HASH_SCHEME);
+        assert_ne!(root_hash(&old), root_hash(&new));
+    }
+
+    #[test]
+    fn apply_changes_matches_rebuild_with_declared_directories() {
+        let old = tree_with_dirs(
+            &[("a/x.ts", "h1"), ("a/y.ts", "h2"), ("b/z.ts", "h3"), ("d/w.ts", "h4")],
+            &["d", "e"],
+        );
+        let mut declare_d = change("d", None);
+        declare_d.deleted = None;
+        declare_d.is_directory = Some(true);
+        let changes = vec![
+            change("a/x.ts", None),
+            change("b/z.ts", Some("h5")),
+            change("c/new.ts", Some("h6")),
+            declare_d,
+            change("d/w.ts", None),
+            change("f/tmp.ts", Some("h7")),
+            change("f/tmp.ts", None),
+        ];
+
+        let updated = apply_changes(old, changes).unwrap();
+        let rebuilt = tree_with_dirs(
+            &[("a/y.ts", "h2"), ("b/z.ts", "h5"), ("c/new.ts", "h6")],
+            &["d", "e"],
+        );
+        assert_eq!(updated.root_hash, Some(root_hash(&rebuilt)));
+        assert_eq!(summarize(updated.nodes), summarize(rebuilt));
+    }
+
+    #[test]
+    fn diff_detects_same_directory_rename() {
+        let old = tree(&[("a.txt", "h1"), ("c.txt", "h2")], LEGACY_HASH_SCHEME);
+        let new = tree(&[("b.txt", "h1"), ("c.txt", "h2")], LEGACY_HASH_SCHEME);
+        let options = DiffOptions {
+            detect_renames: Some(true),
+            ..Default::default()
+        };
+
+        let diff = diff_merkle_trees(old, new, Some(options)).unwrap();
+        let renamed = diff.renamed.unwrap();
+        assert_eq!(renamed.len(), 1);
+        assert_eq!((renamed[0].from.as_str(), renamed[0].to.as_str()), ("a.txt", "b.txt"));
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+    }
+
+    #[test]
+    fn diff_reports_rename_with_equal_roots() {
+        let old = tree(&[("a.txt", "h1")], LEGACY_HASH_SCHEME);
+        let new = tree(&[("b.txt", "h1")], LEGACY_HASH_SCHEME);
+        assert_eq!(root_hash(&old), root_hash(&new));
+
+        let diff = diff_merkle_trees(old, new, None).unwrap();
+        assert_eq!(diff.added, vec!["b.txt"]);
+        assert_eq!(diff.removed, vec!["a.txt"]);
+        assert!(diff.modified.is_empty());
+    }
+
+    #[test]
+    fn diff_reports_files_swapped_between_directories() {
+        let old = tree(&[("src/x.ts", "h1"), ("lib/y.ts", "h2")], LEGACY_HASH_SCHEME);
+        let new = tree(&[("src/y.ts", "h2"), ("lib/x.ts", "h1")], LEGACY_HASH_SCHEME);
+
+        let diff = diff_merkle_trees(old, new, None).unwrap();
+        assert_eq!(diff.added, vec!["lib/x.ts", "src/y.ts"]);
+        assert_eq!(diff.removed, vec!["lib/y.ts", "src/x.ts"]);
+    }
+
+    #[test]
+    fn diff_of_equal_named_trees_is_empty() {
+        let old = tree(&[("a.txt", "h1"), ("src/b.ts", "h2")], NAMED_HASH_SCHEME);
+        let new = tree(&[("src/b.ts", "h2"), ("a.txt", "h1")], NAMED_HASH_SCHEME);
+
+        let diff = diff_merkle_trees(old, new, None).unwrap();
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.modified.is_empty());
+    }
+
+    #[test]
+    fn namespace_salts_file_hashes() {
+        let build = |files: &[(&str, &str)], namespace: &str| {
+            let files = files
+                .iter()
+                .map(|(path, hash)| FileHashEntry {
+                    path: path.to_string(),
+                    hash: hash.to_string(),
+                    is_directory: None,
+                    size: None,
+                })
+                .collect();
+            let options = MerkleOptions {
+                hash_scheme: Some(NAMED_HASH_SCHEME),
+                namespace: Some(namespace.to_string()),
+                ..Default::default()
+            };
+            build_merkle_tree(files, Some(options)).unwrap()
+        };
+        let files = [("a.txt", "h1"), ("src/b.ts", "h2")];
+        let one = build(&files, "workspace-1");
+        let two = build(&files, "workspace-2");
+        let hashes = |nodes: &[MerkleNode]| -> BTreeSet<String> {
+            nodes.iter().map(|n| n.hash.clone()).collect()
+        };
+        assert!(hashes(&one).is_disjoint(&hashes(&two)));
+        assert!(!hashes(&one).contains("h1") && !hashes(&one).contains("h2"));
+
+        let proof = get_merkle_proof(one.clone(), "src/b.ts".to_string()).unwrap();
+        let root = root_hash(&one);
+        assert!(verify_merkle_proof(root.clone(), "src/b.ts".into(), "h2".into(), proof.clone()));
+        assert!(!verify_merkle_proof(root, "src/b.ts".into(), "h3".into(), proof));
+
+        let updated = apply_changes(one, vec![change("src/b.ts", Some("h3"))]).unwrap();
+        let rebuilt = build(&[("a.txt", "h1"), ("src/b.ts", "h3")], "workspace-1");
+        assert_eq!(updated.root_hash, Some(root_hash(&rebuilt)));
+    }
+
+    #[test]
+    fn named_scheme_roots_differ_after_rename() {
+        let old = tree(&[("a.txt", "h1")], NAMED_HASH_SCHEME);
+        let new = tree(&[("b.txt", "h1")], NAMED_HASH_SCHEME);
+        assert_ne!(root_hash(&old), root_hash(&new));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::scanner::{walk, ScanOptions};
+use napi::{Env, JsBuffer};
+use napi_derive::napi;
+
+/// `scan_directory_with_options` packed into one buffer: the file paths as
+/// UTF-8, each followed by a NUL byte (paths cannot contain NUL). Decode with
+/// `TextDecoder` and split on `"\0"`.
+///
+/// The buffer is allocated by the JS engine rather than wrapping native memory,
+/// so a worker can hand it to another thread by listing its `.buffer` in the
+/// `postMessage` transfer list, without a structured-clone copy.
+#[napi(catch_unwind)]
+pub fn scan_directory_packed(
+    env: Env,
+    root_path: String,
+    options: Option<ScanOptions>,
+) -> napi::Result<JsBuffer> {
+    let scan = walk(&root_path, &options.unwrap_or_default())?;
+    let mut packed = Vec::with_capacity(scan.files.iter().map(|f| f.len() + 1).sum());
+    for file in &scan.files {
+        packed.extend_from_slice(file.as_bytes());
+        packed.push(0);
+    }
+    transferable(env, packed)
+}
+
+/// `chunk_file_cdc` packed into one buffer of fixed-size records, little-endian:
+///
+/// ```text
+/// u32:count u32:digest_len
+/// count x { f64:offset u32:length digest_len bytes:digest }
+/// ```
+///
+/// Offsets are f64 so they can be read with a `DataView` without BigInt.
+/// Transferable like `scan_directory_packed`.
+#[cfg(feature = "cdc")]
+#[napi(catch_unwind)]
+pub fn chunk_file_cdc_packed(
+    env: Env,
+    file_path: String,
+    options: Option<crate::cdc::CdcOptions>,
+) -> napi::Result<JsBuffer> {
+    let chunks = crate::cdc::chunk_file_cdc(file_path, options)?;
+    let digests: Vec<Vec<u8>> = chunks
+        .iter()
+        .map(|c| hex::decode(&c.hash).unwrap_or_default())
+        .collect();
+    let digest_len = digests.first().map_or(0, Vec::len);
+
+    let mut packed = Vec::with_capacity(8 + chunks.len() * (12 + digest_len));
+    packed.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    packed.extend_from_slice(&(digest_len as u32).to_le_bytes());
+    for (chunk, digest) in chunks.iter().zip(&digests) {
+        packed.extend_from_slice(&(chunk.offset as f64).to_le_bytes());
+        packed.extend_from_slice(&chunk.length.to_le_bytes());
+        packed.extend_from_slice(digest);
+    }
+    transferable(env, packed)
+}
+
+/// Copy into a buffer owned by the JS engine; buffers wrapping native memory
+/// cannot be detached and are cloned when posted to another thread
+fn transferable(env: Env, bytes: Vec<u8>) -> napi::Result<JsBuffer> {
+    Ok(env.create_buffer_copy(bytes)?.into_raw())
+}
@@ -0,0 +1,193 @@
+use crate::vectors::{dot, Embeddings};
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+
+const DEFAULT_MAX_BYTES: u32 = 16 * 1024;
+const DEFAULT_MAX_LINE_LENGTH: u32 = 1000;
+const DEFAULT_MAX_SYMBOL_RATIO: f64 = 0.5;
+const DEFAULT_MAX_ENCODED_RATIO: f64 = 0.5;
+const DEFAULT_OUTLIER_Z_SCORE: f64 = 3.0;
+
+/// Runs of base64/hex characters at least this long count as encoded data
+const MIN_ENCODED_RUN: usize = 64;
+
+/// Options for `detect_low_quality_chunks`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct ChunkQualityOptions {
+    /// Chunks longer than this many bytes are `tooLong` (default 16 KiB)
+    pub max_bytes: Option<u32>,
+    /// Chunks with a line longer than this many bytes have `longLines`, typical
+    /// of minified or generated code (default 1000)
+    pub max_line_length: Option<u32>,
+    /// Highest share of punctuation among non-whitespace characters (default 0.5)
+    pub max_symbol_ratio: Option<f64>,
+    /// Highest share of non-whitespace characters inside long base64 or hex runs
+    /// (default 0.5)
+    pub max_encoded_ratio: Option<f64>,
+    /// Standard deviations below the mean similarity to the centroid at which an
+    /// embedding is an `outlier` (default 3)
+    pub outlier_z_score: Option<f64>,
+}
+
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkIssue {
+    TooLong,
+    LongLines,
+    /// Mostly punctuation, e.g. ASCII art or separator lines
+    Symbols,
+    /// Mostly base64 or hex, e.g. inlined images or keys
+    Encoded,
+    /// Embedding far from the rest of the collection (or a zero vector)
+    Outlier,
+}
+
+/// A chunk with at least one issue
+#[napi(object)]
+#[derive(Debug)]
+pub struct FlaggedChunk {
+    /// Index into the input chunks
+    pub index: u32,
+    pub issues: Vec<ChunkIssue>,
+    /// Share of punctuation among non-whitespace characters
+    pub symbol_ratio: f64,
+    /// Share of non-whitespace characters inside long base64 or hex runs
+    pub encoded_ratio: f64,
+    /// Cosine similarity to the mean embedding (with embeddings)
+    pub centroid_similarity: Option<f64>,
+}
+
+/// Flag chunks that are unlikely to help retrieval: overly long, minified,
+/// mostly punctuation or encoded data, or (given one packed f32 embedding per
+/// chunk, as for `similarity_matrix`) embedding-space outliers. Only flagged
+/// chunks are returned, in input order.
+#[napi(catch_unwind)]
+pub fn detect_low_quality_chunks(
+    chunks: Vec<String>,
+    embeddings: Option<Buffer>,
+    options: Option<ChunkQualityOptions>,
+) -> napi::Result<Vec<FlaggedChunk>> {
+    use rayon::prelude::*;
+
+    if chunks.is_empty() {
+        return Ok(vec![]);
+    }
+    let options = options.unwrap_or_default();
+    let similarities = match embeddings {
+        Some(buffer) => Some(centroid_similarities(&buffer, chunks.len())?),
+        None => None,
+    };
+    let outlier_below = similarities.as_ref().map(|s| {
+        outlier_threshold(s, options.outlier_z_score.unwrap_or(DEFAULT_OUTLIER_Z_SCORE))
+    });
+
+    let max_bytes = options.max_bytes.unwrap_or(DEFAULT_MAX_BYTES) as usize;
+    let max_line_length = options.max_line_length.unwrap_or(DEFAULT_MAX_LINE_LENGTH) as usize;
+    let max_symbol_ratio = options.max_symbol_ratio.unwrap_or(DEFAULT_MAX_SYMBOL_RATIO);
+    let max_encoded_ratio = options.max_encoded_ratio.unwrap_or(DEFAULT_MAX_ENCODED_RATIO);
+
+    Ok(chunks
+        .par_iter()
+        .enumerate()
+        .filter_map(|(index, text)| {
+            let bytes = text.as_bytes();
+            let (symbol_ratio, encoded_ratio) = character_ratios(bytes);
+            let similarity = similarities.as_ref().map(|s| s[index]);
+
+            let mut issues = Vec::new();
+            if bytes.len() > max_bytes {
+                issues.push(ChunkIssue::TooLong);
+            }
+            if bytes.split(|&b| b == b'\n').any(|line| line.len() > max_line_length) {
+                issues.push(ChunkIssue::LongLines);
+            }
+            if symbol_ratio > max_symbol_ratio {
+                issues.push(ChunkIssue::Symbols);
+            }
+            if encoded_ratio > max_encoded_ratio {
+                issues.push(ChunkIssue::Encoded);
+            }
+            if let (Some(similarity), Some(below)) = (similarity, outlier_below) {
+                if similarity < below || similarity == 0.0 {
+                    issues.push(ChunkIssue::Outlier);
+                }
+            }
+            (!issues.is_empty()).then(|| FlaggedChunk {
+                index: index as u32,
+                issues,
+                symbol_ratio,
+                encoded_ratio,
+                centroid_similarity: similarity.map(f64::from),
+            })
+        })
+        .collect())
+}
+
+/// Shares of punctuation and of long base64/hex runs among non-whitespace bytes
+fn character_ratios(bytes: &[u8]) -> (f64, f64) {
+    let mut visible = 0usize;
+    let mut symbols = 0usize;
+    let mut encoded = 0usize;
+    for token in bytes.split(|b| b.is_ascii_whitespace()) {
+        visible += token.len();
+        symbols += token.iter().filter(|b| b.is_ascii_punctuation()).count();
+        // Long identifiers are rare; long runs with mixed letters and digits are data
+        for run in token.split(|&b| !is_encoded_byte(b)) {
+            if run.len() >= MIN_ENCODED_RUN
+                && run.iter().any(u8::is_ascii_digit)
+                && run.iter().any(u8::is_ascii_alphabetic)
+            {
+                encoded += run.len();
+            }
+        }
+    }
+    if visible == 0 {
+        return (0.0, 0.0);
+    }
+    (symbols as f64 / visible as f64, encoded as f64 / visible as f64)
+}
+
+/// Base64 (standard and URL-safe) alphabet, which covers hex
+fn is_encoded_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=' | b'-' | b'_')
+}
+
+/// Cosine similarity of each embedding to the normalized mean embedding
+fn centroid_similarities(buffer: &[u8], count: usize) -> napi::Result<Vec<f32>> {
+    if buffer.is_empty() || !buffer.len().is_multiple_of(count * 4) {
+        return Err(napi::Error::from_reason(format!(
+            "embeddings hold {} bytes, not one f32 vector for each of {} chunks",
+            buffer.len(),
+            count
+        )));
+    }
+    let dim = (buffer.len() / (count * 4)) as u32;
+    let vectors = Embeddings::parse(buffer, dim, "embeddings")?.normalized();
+    let mut centroid = vec![0f32; vectors.dim];
+    for i in 0..vectors.len() {
+        for (sum, x) in centroid.iter_mut().zip(vectors.row(i)) {
+            *sum += x;
+        }
+    }
+    let norm = dot(&centroid, &centroid).sqrt();
+    if norm > 0.0 {
+        centroid.iter_mut().for_each(|x| *x /= norm);
+    }
+    Ok((0..vectors.len()).map(|i| dot(vectors.row(i), &centroid)).collect())
+}
+
+/// Mean minus `z` standard deviations of the non-zero similarities
+fn outlier_threshold(similarities: &[f32], z: f64) -> f32 {
+    let values: Vec<f64> = similarities
+        .iter()
+        .filter(|&&s| s != 0.0)
+        .map(|&s| s as f64)
+        .collect();
+    if values.len() < 3 {
+        return f32::NEG_INFINITY;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    (mean - z * variance.sqrt()) as f32
+}
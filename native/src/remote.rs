@@ -0,0 +1,332 @@
+use crate::merkle::{parent_path, root_node, MerkleDiff, MerkleNode};
+use napi::bindgen_prelude::FromNapiValue;
+use napi::{Env, JsDeferred, JsFunction, JsObject, JsUnknown, NapiRaw};
+use napi_derive::napi;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::rc::Rc;
+
+/// A child of a remote directory, as reported by the server
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct RemoteEntry {
+    /// Path relative to the tree root, like `MerkleNode::path`
+    pub path: String,
+    pub hash: String,
+    pub is_file: bool,
+}
+
+/// Top-down comparison of a local tree with a remote one that can only be
+/// queried one directory at a time. The comparison does no I/O itself:
+///
+/// ```text
+/// const cmp = new RemoteComparison(localNodes, remoteRootHash);
+/// while (!cmp.isDone) {
+///   await Promise.all(cmp.nextProbes(16).map(async (dir) =>
+///     cmp.supply(dir, await server.children(dir))));
+/// }
+/// const diff = cmp.result();
+/// ```
+///
+/// Only directories whose hashes differ are probed. The diff treats the remote
+/// as the old side: `added` files exist only locally and `removed` only remotely.
+/// Hashes must use the local tree's algorithm, hash scheme and namespace.
+#[napi]
+pub struct RemoteComparison {
+    local: BTreeMap<String, MerkleNode>,
+    /// Directories waiting to be handed out by `next_probes`
+    queue: VecDeque<String>,
+    /// Directories handed out but not yet supplied
+    outstanding: BTreeSet<String>,
+    probed: Vec<String>,
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<String>,
+}
+
+#[napi]
+impl RemoteComparison {
+    /// Compare `nodes` against a remote tree with root hash `remote_root_hash`;
+    /// None for a remote that has no tree yet
+    #[napi(constructor, catch_unwind)]
+    pub fn new(nodes: Vec<MerkleNode>, remote_root_hash: Option<String>) -> Self {
+        let local_root = root_node(&nodes).map(|n| (n.path.clone(), n.hash.clone()));
+        let mut comparison = RemoteComparison {
+            local: nodes.into_iter().map(|n| (n.path.clone(), n)).collect(),
+            queue: VecDeque::new(),
+            outstanding: BTreeSet::new(),
+            probed: Vec::new(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+        };
+        match (local_root, remote_root_hash) {
+            (Some((_, local)), Some(remote)) if local == remote => {}
+            (_, Some(_)) => comparison.queue.push_back(".".to_string()),
+            (Some((root, _)), None) => comparison.add_local_subtree(&root),
+            (None, None) => {}
+        }
+        comparison
+    }
+
+    /// Whether every needed directory has been supplied
+    #[napi(getter, catch_unwind)]
+    pub fn is_done(&self) -> bool {
+        self.queue.is_empty() && self.outstanding.is_empty()
+    }
+
+    /// Up to `max` directories (default all pending) whose remote children are
+    /// needed next; each must be answered with `supply`
+    #[napi(catch_unwind)]
+    pub fn next_probes(&mut self, max: Option<u32>) -> Vec<String> {
+        let count = max.map_or(self.queue.len(), |m| (m as usize).min(self.queue.len()));
+        let probes: Vec<String> = self.queue.drain(..count).collect();
+        self.outstanding.extend(probes.iter().cloned());
+        probes
+    }
+
+    /// Report the remote children of a probed directory. Differing files are
+    /// recorded and differing subdirectories become new probes.
+    #[napi(catch_unwind)]
+    pub fn supply(&mut self, directory: String, children: Vec<RemoteEntry>) -> napi::Result<()> {
+        if !self.outstanding.remove(&directory) {
+            return Err(napi::Error::from_reason(format!(
+                "{} was not requested by nextProbes",
+                directory
+            )));
+        }
+        if let Some(child) = children.iter().find(|c| parent_path(&c.path) != directory) {
+            return Err(napi::Error::from_reason(format!(
+                "{} is not a child of {}",
+                child.path, directory
+            )));
+        }
+        self.probed.push(directory.clone());
+
+        let local_children: BTreeSet<String> = self
+            .local
+            .get(&directory)
+            .filter(|n| !n.is_file)
+            .map(|n| n.children.iter().cloned().collect())
+            .unwrap_or_default();
+        let remote_paths: BTreeSet<&str> = children.iter().map(|c| c.path.as_str()).collect();
+
+        for remote in &children {
+            let local = self.local.get(&remote.path);
+            if local.is_some_and(|l| l.hash == remote.hash && l.is_file == remote.is_file) {
+                continue;
+            }
+            match (local.map(|l| l.is_file), remote.is_file) {
+                (Some(true), true) => self.modified.push(remote.path.clone()),
+                (Some(false), false) => self.queue.push_back(remote.path.clone()),
+                (local_kind, true) => {
+                    self.removed.push(remote.path.clone());
+                    if local_kind == Some(false) {
+                        self.add_local_subtree(&remote.path);
+                    }
+                }
+                (local_kind, false) => {
+                    // Remote-only directory: probe it to list its files as removed
+                    self.queue.push_back(remote.path.clone());
+                    if local_kind == Some(true) {
+                        self.added.push(remote.path.clone());
+                    }
+                }
+            }
+        }
+        for path in local_children {
+            if !remote_paths.contains(path.as_str()) {
+                self.add_local_subtree(&path);
+            }
+        }
+        Ok(())
+    }
+
+    /// The file-level diff; fails while probes are pending
+    #[napi(catch_unwind)]
+    pub fn result(&self) -> napi::Result<MerkleDiff> {
+        if !self.is_done() {
+            return Err(napi::Error::from_reason(format!(
+                "Comparison incomplete: {} directories pending",
+                self.queue.len() + self.outstanding.len()
+            )));
+        }
+        let sorted = |paths: &Vec<String>| {
+            let mut paths = paths.clone();
+            paths.sort();
+            paths
+        };
+        Ok(MerkleDiff {
+            added: sorted(&self.added),
+            removed: sorted(&self.removed),
+            modified: sorted(&self.modified),
+            changed_directories: Some(sorted(&self.probed)),
+            renamed: None,
+        })
+    }
+}
+
+impl RemoteComparison {
+    /// Record every local file at or below `path` as added
+    fn add_local_subtree(&mut self, path: &str) {
+        let mut stack = vec![path.to_string()];
+        while let Some(path) = stack.pop() {
+            match self.local.get(&path) {
+                Some(node) if node.is_file => self.added.push(path),
+                Some(node) => stack.extend(node.children.iter().cloned()),
+                None => {}
+            }
+        }
+    }
+}
+
+/// Options for `compare_with_remote`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct RemoteCompareOptions {
+    /// Root hash of the remote tree, when known; equal roots finish without a
+    /// probe. Without it "." is always probed.
+    pub remote_root_hash: Option<String>,
+    /// Probes awaited at once (default 16)
+    pub concurrency: Option<u32>,
+}
+
+const DEFAULT_PROBE_CONCURRENCY: u32 = 16;
+
+/// Compare `nodes` with a remote tree through `probe`, which receives a
+/// directory path and returns (a Promise of) its remote children as
+/// `RemoteEntry[]`. Drives a `RemoteComparison` to completion, probing only
+/// directories whose hashes differ, and resolves to its `result()`. The
+/// returned Promise rejects when a probe throws, rejects or returns children
+/// that `supply` refuses; answers to probes still in flight are then ignored.
+#[napi(catch_unwind)]
+pub fn compare_with_remote(
+    env: Env,
+    nodes: Vec<MerkleNode>,
+    probe: JsFunction,
+    options: Option<RemoteCompareOptions>,
+) -> napi::Result<JsObject> {
+    let options = options.unwrap_or_default();
+    // An empty hash never matches, so an unknown remote root is probed
+    let remote_root_hash = Some(options.remote_root_hash.unwrap_or_default());
+    let (deferred, promise) = env.create_deferred()?;
+    let driver = Rc::new(RemoteDriver {
+        comparison: RefCell::new(RemoteComparison::new(nodes, remote_root_hash)),
+        deferred: RefCell::new(Some(deferred)),
+        in_flight: Cell::new(0),
+        concurrency: options
+            .concurrency
+            .unwrap_or(DEFAULT_PROBE_CONCURRENCY)
+            .max(1),
+    });
+    if let Err(err) = RemoteDriver::pump(&driver, env, &probe) {
+        driver.settle(Err(err));
+    }
+    Ok(promise)
+}
+
+type DiffResolver = Box<dyn FnOnce(Env) -> napi::Result<MerkleDiff>>;
+
+/// State of one `compare_with_remote` call, shared by the callbacks attached
+/// to its probes; everything runs on the JS thread. The probe function is
+/// bound into those callbacks rather than referenced from here, so nothing
+/// needs releasing when a probe never settles.
+struct RemoteDriver {
+    comparison: RefCell<RemoteComparison>,
+    /// Taken when the result is settled
+    deferred: RefCell<Option<JsDeferred<MerkleDiff, DiffResolver>>>,
+    in_flight: Cell<u32>,
+    concurrency: u32,
+}
+
+impl RemoteDriver {
+    /// Start probes up to the concurrency limit, or settle when none are left
+    fn pump(driver: &Rc<Self>, env: Env, probe: &JsFunction) -> napi::Result<()> {
+        if driver.deferred.borrow().is_none() {
+            return Ok(());
+        }
+        let free = driver.concurrency - driver.in_flight.get();
+        let probes = driver.comparison.borrow_mut().next_probes(Some(free));
+        for directory in probes {
+            Self::start(driver, env, probe, directory)?;
+        }
+        if driver.in_flight.get() == 0 {
+            let result = driver.comparison.borrow().result();
+            driver.settle(result);
+        }
+        Ok(())
+    }
+
+    /// Call `probe` for `directory` and supply its answer once it resolves
+    fn start(
+        driver: &Rc<Self>,
+        env: Env,
+        probe: &JsFunction,
+        directory: String,
+    ) -> napi::Result<()> {
+        let answer: JsUnknown = probe.call1(directory.clone())?;
+        // Promise.resolve also accepts probes that answer synchronously
+        let promise_class = env
+            .get_global()?
+            .get_named_property::<JsFunction>("Promise")?
+            .coerce_to_object()?;
+        let resolve: JsFunction = promise_class.get_named_property("resolve")?;
+        let promise = resolve.call(Some(&promise_class), &[answer])?.coerce_to_object()?;
+
+        let fulfilled = {
+            let driver = driver.clone();
+            let directory = directory.clone();
+            env.create_function_from_closure("onProbeFulfilled", move |ctx| {
+                driver.in_flight.set(driver.in_flight.get() - 1);
+                let probe = ctx.this::<JsFunction>()?;
+                let supplied = ctx.get::<JsUnknown>(0).and_then(|children| {
+                    // SAFETY: the value belongs to this callback's env and scope
+                    let children = unsafe {
+                        Vec::<RemoteEntry>::from_napi_value(ctx.env.raw(), children.raw())
+                    }?;
+                    driver.comparison.borrow_mut().supply(directory.clone(), children)
+                });
+                if let Err(err) = supplied.and_then(|_| Self::pump(&driver, *ctx.env, &probe)) {
+                    driver.settle(Err(err));
+                }
+                Ok(())
+            })?
+            .coerce_to_object()?
+        };
+        // Bind the probe as `this`, for the probes this answer unlocks
+        let bind: JsFunction = fulfilled.get_named_property("bind")?;
+        let fulfilled = bind.call(Some(&fulfilled), std::slice::from_ref(probe))?;
+        let rejected = {
+            let driver = driver.clone();
+            env.create_function_from_closure("onProbeRejected", move |ctx| {
+                driver.in_flight.set(driver.in_flight.get() - 1);
+                let reason = ctx
+                    .get::<JsUnknown>(0)
+                    .and_then(|reason| reason.coerce_to_string())
+                    .and_then(|reason| reason.into_utf8())
+                    .and_then(|reason| reason.into_owned())
+                    .unwrap_or_default();
+                driver.settle(Err(napi::Error::from_reason(format!(
+                    "Probe of {} failed: {}",
+                    directory, reason
+                ))));
+                Ok(())
+            })?
+        };
+        let then: JsFunction = promise.get_named_property("then")?;
+        then.call(Some(&promise), &[fulfilled, rejected.into_unknown()])?;
+        driver.in_flight.set(driver.in_flight.get() + 1);
+        Ok(())
+    }
+
+    /// Resolve or reject the returned Promise, once
+    fn settle(&self, result: napi::Result<MerkleDiff>) {
+        let Some(deferred) = self.deferred.borrow_mut().take() else {
+            return;
+        };
+        match result {
+            Ok(diff) => deferred.resolve(Box::new(move |_| Ok(diff))),
+            Err(err) => deferred.reject(err),
+        }
+    }
+}
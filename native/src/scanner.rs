@@ -1,13 +1,148 @@
+use crate::crash::trace_event;
+use crate::hasher::{checked_options, hash_entry, HashFailure, HashOptions};
 use napi_derive::napi;
 use ignore::WalkBuilder;
-use std::path::Path;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, UNIX_EPOCH};
+
+/// Options for `scan_directory_with_options`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct ScanOptions {
+    /// File extensions to include (leading dot optional); empty or absent includes all
+    pub extensions: Option<Vec<String>>,
+    /// Skip any directory holding more than this many entries (the root is never skipped)
+    pub max_entries_per_dir: Option<u32>,
+    /// Ordering of the returned files (default: lexicographic)
+    pub order: Option<ScanOrder>,
+    /// Skip files larger than this many bytes
+    pub max_file_size: Option<i64>,
+    /// Skip files that look binary (NUL byte in the first 8000 bytes)
+    pub skip_binary: Option<bool>,
+    /// Count hidden and ignore-file exclusions in the report. Lists every visited
+    /// directory once more, so it is off by default and both counts stay 0.
+    pub count_ignored: Option<bool>,
+    /// File names (e.g. `.noindex`) that exclude the directory holding them and
+    /// everything below it, including the root
+    pub stop_files: Option<Vec<String>>,
+}
+
+/// Ordering of scan output, computed on `/`-separated relative paths so it is
+/// identical across platforms and independent of filesystem enumeration order
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum ScanOrder {
+    /// Byte-wise lexicographic order of the relative path
+    #[default]
+    Lexicographic,
+    /// Depth-first order with subdirectories listed before files at each level
+    DirectoriesFirst,
+}
+
+/// A directory that was not descended into because it exceeded `max_entries_per_dir`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SkippedDirectory {
+    pub path: String,
+    /// Number of entries found in the directory
+    pub entry_count: u32,
+}
+
+/// Result of `scan_directory_with_options`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct ScanResult {
+    /// Matched file paths, sorted
+    pub files: Vec<String>,
+    /// Directories skipped by the per-directory entry cap
+    pub skipped_directories: Vec<SkippedDirectory>,
+    /// Directories excluded by one of `stop_files`, sorted
+    pub stopped_directories: Vec<String>,
+    /// Traversal statistics
+    pub report: ScanReport,
+}
+
+/// Traversal statistics for a scan, answering "why is my file not indexed?"
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct ScanReport {
+    pub directories_visited: u32,
+    pub files_matched: u32,
+    /// Entries excluded by .gitignore/.cursorignore rules (an ignored directory
+    /// counts once); only with `count_ignored`
+    pub ignored_by_gitignore: u32,
+    /// Hidden entries (dotfiles and dot-directories); only with `count_ignored`
+    pub ignored_hidden: u32,
+    /// Files whose extension is not in `extensions`
+    pub ignored_by_extension: u32,
+    /// Files larger than `max_file_size`
+    pub ignored_by_size: u32,
+    /// Files detected as binary when `skip_binary` is set
+    pub ignored_binary: u32,
+    /// Entries that could not be read; the scan continues past them
+    pub errors: Vec<ScanError>,
+    /// Wall time of the scan in milliseconds
+    pub elapsed_ms: f64,
+}
+
+/// An error encountered while walking
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct ScanError {
+    pub path: Option<String>,
+    pub message: String,
+}
 
 /// Scan a directory and return all indexable file paths.
 /// Respects .gitignore and .cursorignore rules.
 /// Filters to only include files with specified extensions.
-#[napi]
+#[napi(catch_unwind)]
 pub fn scan_directory(root_path: String, extensions: Vec<String>) -> napi::Result<Vec<String>> {
-    let root = Path::new(&root_path);
+    let options = ScanOptions {
+        extensions: Some(extensions),
+        ..Default::default()
+    };
+    Ok(walk_entries(&root_path, &options, true)?.files)
+}
+
+/// Scan a directory like `scan_directory`, with additional filters and limits.
+/// Unreadable entries are recorded in the report instead of failing the scan, and
+/// directories skipped by a limit are reported so the caller can warn the user.
+#[napi(catch_unwind)]
+pub fn scan_directory_with_options(
+    root_path: String,
+    options: Option<ScanOptions>,
+) -> napi::Result<ScanResult> {
+    walk(&root_path, &options.unwrap_or_default())
+}
+
+pub(crate) fn walk(root_path: &str, options: &ScanOptions) -> napi::Result<ScanResult> {
+    walk_entries(root_path, options, false)
+}
+
+/// Non-hidden entries of a visited directory, and how many of them the walker
+/// yielded or a limit skipped
+#[derive(Default)]
+struct DirTally {
+    listed: u32,
+    reached: u32,
+}
+
+/// `walk`, optionally failing on the first unreadable entry instead of
+/// recording it in the report
+fn walk_entries(
+    root_path: &str,
+    options: &ScanOptions,
+    fail_fast: bool,
+) -> napi::Result<ScanResult> {
+    trace_event(format!("scan {}", root_path));
+    let started = Instant::now();
+    let root = Path::new(root_path);
     if !root.is_dir() {
         return Err(napi::Error::from_reason(format!(
             "Not a directory: {}",
@@ -15,46 +150,308 @@ pub fn scan_directory(root_path: String, extensions: Vec<String>) -> napi::Resul
         )));
     }
 
-    let ext_set: std::collections::HashSet<String> = extensions
-        .into_iter()
+    let ext_set: HashSet<String> = options
+        .extensions
+        .iter()
+        .flatten()
         .map(|e| e.trim_start_matches('.').to_lowercase())
         .collect();
 
     let mut files = Vec::new();
+    let mut report = ScanReport::default();
+    let count_ignored = options.count_ignored == Some(true);
+    let mut tallies: HashMap<PathBuf, DirTally> = HashMap::new();
+    let skipped = Arc::new(Mutex::new(Vec::new()));
+    let stopped = Arc::new(Mutex::new(Vec::new()));
+    let stop_files: Vec<String> = options.stop_files.clone().unwrap_or_default();
+
+    if has_stop_file(root, &stop_files) {
+        report.elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+        return Ok(ScanResult {
+            files,
+            skipped_directories: vec![],
+            stopped_directories: vec![root_path.to_string()],
+            report,
+        });
+    }
 
-    let walker = WalkBuilder::new(&root_path)
+    let mut builder = WalkBuilder::new(root_path);
+    builder
         .hidden(true) // skip hidden files/dirs
         .git_ignore(true) // respect .gitignore
         .git_global(true)
         .git_exclude(true)
-        .add_custom_ignore_filename(".cursorignore")
-        .build();
+        .add_custom_ignore_filename(".cursorignore");
 
-    for entry in walker {
-        let entry = entry.map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    let cap = options.max_entries_per_dir;
+    if cap.is_some() || !stop_files.is_empty() {
+        let skipped = Arc::clone(&skipped);
+        let stopped = Arc::clone(&stopped);
+        builder.filter_entry(move |entry| {
+            if entry.depth() == 0 || !entry.file_type().is_some_and(|t| t.is_dir()) {
+                return true;
+            }
+            if has_stop_file(entry.path(), &stop_files) {
+                stopped
+                    .lock()
+                    .unwrap()
+                    .push(entry.path().to_string_lossy().into_owned());
+                return false;
+            }
+            let Some(cap) = cap else {
+                return true;
+            };
+            let count = match fs::read_dir(entry.path()) {
+                Ok(rd) => rd.count(),
+                Err(_) => return true, // let the walker surface the error
+            };
+            if count <= cap as usize {
+                return true;
+            }
+            skipped.lock().unwrap().push(SkippedDirectory {
+                path: entry.path().to_string_lossy().into_owned(),
+                entry_count: count.min(u32::MAX as usize) as u32,
+            });
+            false
+        });
+    }
+
+    for entry in builder.build() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if fail_fast => return Err(napi::Error::from_reason(err.to_string())),
+            Err(err) => {
+                report.errors.push(ScanError {
+                    path: error_path(&err),
+                    message: err.to_string(),
+                });
+                continue;
+            }
+        };
         let path = entry.path();
+        if count_ignored {
+            if let Some(tally) = path.parent().and_then(|parent| tallies.get_mut(parent)) {
+                tally.reached += 1;
+            }
+        }
 
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            report.directories_visited += 1;
+            if count_ignored {
+                let tally = list_directory(path, &mut report.ignored_hidden);
+                tallies.insert(path.to_path_buf(), tally);
+            }
+            continue;
+        }
         if !path.is_file() {
             continue;
         }
 
         // Filter by extension
-        if let Some(ext) = path.extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
-            if ext_set.is_empty() || ext_set.contains(&ext_str) {
-                if let Some(path_str) = path.to_str() {
-                    files.push(path_str.to_string());
-                }
+        let ext_matches = path.extension().is_some_and(|ext| {
+            ext_set.is_empty() || ext_set.contains(&ext.to_string_lossy().to_lowercase())
+        });
+        if !ext_matches {
+            report.ignored_by_extension += 1;
+            continue;
+        }
+
+        if let Some(max_size) = options.max_file_size {
+            if entry.metadata().is_ok_and(|m| m.len() as i64 > max_size) {
+                report.ignored_by_size += 1;
+                continue;
             }
         }
+
+        if options.skip_binary == Some(true) && is_binary_file(path) {
+            report.ignored_binary += 1;
+            continue;
+        }
+
+        if let Some(path_str) = path.to_str() {
+            files.push(path_str.to_string());
+        }
+    }
+
+    sort_files(root, &mut files, options.order.unwrap_or_default());
+    let mut skipped_directories = std::mem::take(&mut *skipped.lock().unwrap());
+    skipped_directories.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut stopped_directories = std::mem::take(&mut *stopped.lock().unwrap());
+    stopped_directories.sort();
+
+    // Listed entries that the walker neither yielded nor skipped for a limit or
+    // a stop file were excluded by an ignore file
+    if count_ignored {
+        let excluded = skipped_directories
+            .iter()
+            .map(|d| d.path.as_str())
+            .chain(stopped_directories.iter().map(String::as_str));
+        for dir in excluded {
+            let parent = Path::new(dir).parent();
+            if let Some(tally) = parent.and_then(|parent| tallies.get_mut(parent)) {
+                tally.reached += 1;
+            }
+        }
+        report.ignored_by_gitignore = tallies
+            .values()
+            .map(|t| t.listed.saturating_sub(t.reached))
+            .sum();
     }
+    report.files_matched = files.len() as u32;
+    report.elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
 
-    files.sort();
-    Ok(files)
+    Ok(ScanResult {
+        files,
+        skipped_directories,
+        stopped_directories,
+        report,
+    })
+}
+
+/// List `dir` once, adding its hidden entries to `hidden` and returning the
+/// tally of its other entries (empty if it cannot be read)
+fn list_directory(dir: &Path, hidden: &mut u32) -> DirTally {
+    let mut tally = DirTally::default();
+    for child in fs::read_dir(dir).into_iter().flatten().flatten() {
+        if child.file_name().to_string_lossy().starts_with('.') {
+            *hidden += 1;
+        } else {
+            tally.listed += 1;
+        }
+    }
+    tally
+}
+
+/// Whether `dir` directly contains one of `stop_files`
+fn has_stop_file(dir: &Path, stop_files: &[String]) -> bool {
+    stop_files
+        .iter()
+        .any(|name| fs::symlink_metadata(dir.join(name)).is_ok())
+}
+
+/// The stop file that excludes `file_path` from indexing, checking each directory
+/// from `root_path` down to the file's parent, or None. Lets a watcher drop
+/// events for subtrees the scanner skips.
+#[napi(catch_unwind)]
+pub fn find_stop_file(
+    root_path: String,
+    file_path: String,
+    stop_files: Vec<String>,
+) -> Option<String> {
+    let root = Path::new(&root_path);
+    let relative = Path::new(&file_path).strip_prefix(root).ok()?;
+    let mut dir = root.to_path_buf();
+    let mut parents = vec![dir.clone()];
+    for component in relative.parent()?.components() {
+        dir.push(component);
+        parents.push(dir.clone());
+    }
+    parents.into_iter().find_map(|dir| {
+        stop_files
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|stop| fs::symlink_metadata(stop).is_ok())
+            .map(|stop| stop.to_string_lossy().into_owned())
+    })
+}
+
+/// Heuristic binary check: a NUL byte within the first 8000 bytes (same rule as git)
+pub(crate) fn is_binary_file(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    let mut head = Vec::with_capacity(8000);
+    if file.take(8000).read_to_end(&mut head).is_err() {
+        return false;
+    }
+    head.contains(&0)
+}
+
+fn error_path(err: &ignore::Error) -> Option<String> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path.to_string_lossy().into_owned()),
+        ignore::Error::Loop { child, .. } => Some(child.to_string_lossy().into_owned()),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        _ => None,
+    }
+}
+
+/// A scanned file with its content hash and metadata.
+/// `path` is relative to the scan root with `/` separators, ready for `build_merkle_tree`.
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct ScannedFile {
+    pub path: String,
+    /// Content hash (SHA-256 unless another algorithm was requested)
+    pub hash: String,
+    /// Size in bytes
+    pub size: i64,
+    /// Last modification time in milliseconds since the Unix epoch
+    pub mtime: f64,
+}
+
+/// Result of `scan_and_hash`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct ScanAndHashResult {
+    /// Hashed files, in scan order
+    pub files: Vec<ScannedFile>,
+    /// Files that disappeared or became unreadable mid-scan, in scan order
+    pub failed: Vec<HashFailure>,
+}
+
+/// Walk, filter and hash a directory in a single native pass (hashing is parallel via rayon).
+/// Files that cannot be hashed are listed in `failed` with the reason.
+#[napi(catch_unwind)]
+pub fn scan_and_hash(
+    root_path: String,
+    options: Option<ScanOptions>,
+    hash_options: Option<HashOptions>,
+) -> napi::Result<ScanAndHashResult> {
+    use rayon::prelude::*;
+
+    let hash_options = HashOptions {
+        include_stat: Some(true),
+        ..checked_options(hash_options)?
+    };
+    let scan = walk(&root_path, &options.unwrap_or_default())?;
+    let root = Path::new(&root_path);
+
+    let results: Vec<Result<ScannedFile, HashFailure>> = scan
+        .files
+        .par_iter()
+        .filter_map(|file| {
+            let path = Path::new(file);
+            let hashed = match hash_entry(path, &hash_options) {
+                Ok(hashed) => hashed,
+                Err(err) => return Some(Err(HashFailure::new(file, &err))),
+            };
+            Some(Ok(ScannedFile {
+                path: relative_slash_path(root, path)?,
+                hash: hashed.hash,
+                size: hashed.size?,
+                mtime: hashed.mtime?,
+            }))
+        })
+        .collect();
+
+    let mut result = ScanAndHashResult {
+        files: Vec::with_capacity(results.len()),
+        failed: Vec::new(),
+    };
+    for file in results {
+        match file {
+            Ok(file) => result.files.push(file),
+            Err(failure) => result.failed.push(failure),
+        }
+    }
+    Ok(result)
 }
 
 /// Get relative path from root
-#[napi]
+#[napi(catch_unwind)]
 pub fn get_relative_path(root_path: String, file_path: String) -> Option<String> {
     let root = Path::new(&root_path);
     let file = Path::new(&file_path);
@@ -63,3 +460,88 @@ pub fn get_relative_path(root_path: String, file_path: String) -> Option<String>
         .and_then(|p| p.to_str())
         .map(|s| s.to_string())
 }
+
+fn sort_files(root: &Path, files: &mut Vec<String>, order: ScanOrder) {
+    let mut keyed: Vec<(String, String)> = files
+        .drain(..)
+        .map(|f| (relative_slash_path(root, Path::new(&f)).unwrap_or_else(|| f.clone()), f))
+        .collect();
+    match order {
+        ScanOrder::Lexicographic => keyed.sort_by(|a, b| a.0.cmp(&b.0)),
+        ScanOrder::DirectoriesFirst => keyed.sort_by(|a, b| directories_first(&a.0, &b.0)),
+    }
+    files.extend(keyed.into_iter().map(|(_, f)| f));
+}
+
+fn directories_first(a: &str, b: &str) -> Ordering {
+    let mut a_parts = a.split('/');
+    let mut b_parts = b.split('/');
+    loop {
+        match (a_parts.next(), b_parts.next()) {
+            (Some(x), Some(y)) => {
+                // A component is a directory if more components follow it
+                let x_is_dir = a_parts.clone().next().is_some();
+                let y_is_dir = b_parts.clone().next().is_some();
+                if x == y && x_is_dir == y_is_dir {
+                    continue;
+                }
+                return y_is_dir.cmp(&x_is_dir).then_with(|| x.cmp(y));
+            }
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+        }
+    }
+}
+
+/// Relative path from `root` to `path`, joined with `/` regardless of platform
+pub(crate) fn relative_slash_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Option<Vec<&str>> = relative.components().map(|c| c.as_os_str().to_str()).collect();
+    Some(parts?.join("/"))
+}
+
+/// Modification time in milliseconds since the Unix epoch (0 if unavailable)
+pub(crate) fn mtime_millis(metadata: &fs::Metadata) -> f64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_counts_ignored_entries_on_request() {
+        let root = std::env::temp_dir()
+            .join(format!("codebase-native-test-scan-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["src", "vendor", ".cache"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["src/a.ts", "src/b.log", "vendor/v.ts", ".cache/c.ts", ".env"] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+        fs::write(root.join(".cursorignore"), "vendor/\n*.log\n").unwrap();
+        let mut options = ScanOptions {
+            extensions: Some(vec!["ts".to_string()]),
+            ..Default::default()
+        };
+
+        let plain = walk(&root.to_string_lossy(), &options).unwrap();
+        options.count_ignored = Some(true);
+        let counted = walk(&root.to_string_lossy(), &options).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(plain.files.len(), 1);
+        assert_eq!(plain.report.directories_visited, 2);
+        assert_eq!(plain.report.ignored_hidden + plain.report.ignored_by_gitignore, 0);
+        assert_eq!(counted.files, plain.files);
+        assert_eq!(counted.report.ignored_hidden, 3);
+        assert_eq!(counted.report.ignored_by_gitignore, 2);
+    }
+}
@@ -0,0 +1,187 @@
+#[cfg(feature = "cdc")]
+use crate::cdc::chunk_file_cdc;
+use crate::hasher::{hash_file, hash_string, HashAlgorithm};
+use crate::merkle::{build_merkle_tree, diff_merkle_trees, get_root_hash, FileHashEntry};
+use crate::scanner::{scan_and_hash, walk, ScanOptions};
+use napi_derive::napi;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Outcome of one self-test stage
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SelfTestStage {
+    /// Stage name: `setup`, `scan`, `hash`, `chunk` (with the `cdc` feature) or `merkle`
+    pub name: String,
+    pub passed: bool,
+    pub elapsed_ms: f64,
+    /// Why the stage failed
+    pub error: Option<String>,
+}
+
+/// Result of `run_self_test`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SelfTestReport {
+    /// True when every stage passed
+    pub passed: bool,
+    pub stages: Vec<SelfTestStage>,
+}
+
+/// Exercise the native stages (scan, hash, chunk, merkle) on a tiny synthetic
+/// corpus in the temp directory and report pass/fail per stage with timings.
+/// Storage and search live in the TypeScript layer and are not covered here.
+#[napi(catch_unwind)]
+pub fn run_self_test() -> SelfTestReport {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let dir = std::env::temp_dir().join(format!(
+        "codebase-native-selftest-{}-{}",
+        std::process::id(),
+        nanos
+    ));
+
+    let mut stages = vec![stage("setup", || write_corpus(&dir))];
+    if stages[0].passed {
+        stages.push(stage("scan", || check_scan(&dir)));
+        stages.push(stage("hash", || check_hash(&dir)));
+        #[cfg(feature = "cdc")]
+        stages.push(stage("chunk", || check_chunk(&dir)));
+        stages.push(stage("merkle", || check_merkle(&dir)));
+    }
+    let _ = fs::remove_dir_all(&dir);
+
+    SelfTestReport {
+        passed: stages.iter().all(|s| s.passed),
+        stages,
+    }
+}
+
+fn stage(name: &str, check: impl FnOnce() -> Result<(), String>) -> SelfTestStage {
+    let started = Instant::now();
+    let result = check();
+    SelfTestStage {
+        name: name.to_string(),
+        passed: result.is_ok(),
+        elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: result.err(),
+    }
+}
+
+/// `src/a.ts`, `src/b.ts` and `data.bin` are indexable; the hidden file and the
+/// `.cursorignore`d directory must be skipped by the scanner
+fn write_corpus(dir: &Path) -> Result<(), String> {
+    let write = |relative: &str, content: &[u8]| -> Result<(), String> {
+        let path = dir.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        fs::write(&path, content).map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    write("src/a.ts", b"export const a = 1;\n")?;
+    write("src/b.ts", b"export const b = 2;\n")?;
+    write(".hidden.ts", b"hidden\n")?;
+    write("ignored/c.ts", b"ignored\n")?;
+    write(".cursorignore", b"ignored\n")?;
+    write("data.bin", &synthetic_bytes(200 * 1024))
+}
+
+/// Deterministic pseudo-random bytes (xorshift), so chunk boundaries are content-defined
+fn synthetic_bytes(len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn check_scan(dir: &Path) -> Result<(), String> {
+    let result = walk(&dir.to_string_lossy(), &ScanOptions::default()).map_err(|e| e.reason)?;
+    let mut found: Vec<String> = result
+        .files
+        .iter()
+        .filter_map(|f| Path::new(f).strip_prefix(dir).ok())
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .collect();
+    found.sort();
+    let expected = ["data.bin", "src/a.ts", "src/b.ts"];
+    if found != expected {
+        return Err(format!("expected files {:?}, found {:?}", expected, found));
+    }
+    Ok(())
+}
+
+fn check_hash(dir: &Path) -> Result<(), String> {
+    // Known-answer test: SHA-256("abc")
+    let abc = hash_string("abc".to_string(), Some(HashAlgorithm::Sha256));
+    if abc != "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad" {
+        return Err(format!("SHA-256 known-answer mismatch: {}", abc));
+    }
+    let file = path_string(dir.join("src/a.ts"));
+    let from_file = hash_file(file, None).map_err(|e| e.reason)?;
+    let from_string = hash_string("export const a = 1;\n".to_string(), None);
+    if from_file != from_string {
+        return Err("file hash differs from hash of the same content".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cdc")]
+fn check_chunk(dir: &Path) -> Result<(), String> {
+    let file = dir.join("data.bin");
+    let size = fs::metadata(&file).map_err(|e| e.to_string())?.len();
+    let chunks = chunk_file_cdc(path_string(file), None).map_err(|e| e.reason)?;
+    let total: u64 = chunks.iter().map(|c| c.length as u64).sum();
+    if chunks.is_empty() || total != size {
+        return Err(format!(
+            "{} chunks covering {} of {} bytes",
+            chunks.len(),
+            total,
+            size
+        ));
+    }
+    Ok(())
+}
+
+fn check_merkle(dir: &Path) -> Result<(), String> {
+    let tree = |dir: &Path| -> Result<Vec<_>, String> {
+        let scan = scan_and_hash(path_string(dir.to_path_buf()), None, None).map_err(|e| e.reason)?;
+        let entries = scan
+            .files
+            .into_iter()
+            .map(|f| FileHashEntry {
+                path: f.path,
+                hash: f.hash,
+                is_directory: None,
+                size: Some(f.size),
+            })
+            .collect();
+        build_merkle_tree(entries, None).map_err(|e| e.reason)
+    };
+    let before = tree(dir)?;
+    fs::write(dir.join("src/b.ts"), b"export const b = 3;\n").map_err(|e| e.to_string())?;
+    let after = tree(dir)?;
+
+    if get_root_hash(before.clone()) == get_root_hash(after.clone()) {
+        return Err("root hash did not change after an edit".to_string());
+    }
+    let diff = diff_merkle_trees(before, after, None).map_err(|e| e.reason)?;
+    if diff.modified != ["src/b.ts"] || !diff.added.is_empty() || !diff.removed.is_empty() {
+        return Err(format!(
+            "unexpected diff: added {:?}, removed {:?}, modified {:?}",
+            diff.added, diff.removed, diff.modified
+        ));
+    }
+    Ok(())
+}
+
+fn path_string(path: PathBuf) -> String {
+    path.to_string_lossy().into_owned()
+}
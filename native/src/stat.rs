@@ -0,0 +1,111 @@
+use crate::scanner::mtime_millis;
+use napi_derive::napi;
+use std::fs;
+use std::io;
+
+/// Metadata of one path from `stat_paths`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct PathStat {
+    pub path: String,
+    /// False when the path does not exist (or is a broken symlink)
+    pub exists: bool,
+    pub is_file: Option<bool>,
+    pub is_directory: Option<bool>,
+    /// Size in bytes
+    pub size: Option<i64>,
+    /// Modification time in milliseconds since the Unix epoch
+    pub mtime: Option<f64>,
+    /// Why the path could not be stat'ed, for errors other than not-found
+    pub error: Option<String>,
+}
+
+/// Stat many paths in one call (parallel via rayon), following symlinks.
+/// Results are in input order.
+#[napi(catch_unwind)]
+pub fn stat_paths(paths: Vec<String>) -> Vec<PathStat> {
+    use rayon::prelude::*;
+
+    paths
+        .into_par_iter()
+        .map(|path| match fs::metadata(&path) {
+            Ok(metadata) => PathStat {
+                exists: true,
+                is_file: Some(metadata.is_file()),
+                is_directory: Some(metadata.is_dir()),
+                size: Some(metadata.len() as i64),
+                mtime: Some(mtime_millis(&metadata)),
+                error: None,
+                path,
+            },
+            Err(e) => PathStat {
+                exists: false,
+                is_file: None,
+                is_directory: None,
+                size: None,
+                mtime: None,
+                error: (e.kind() != io::ErrorKind::NotFound).then(|| e.to_string()),
+                path,
+            },
+        })
+        .collect()
+}
+
+/// Outcome of `validate_paths` for one path
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum PathStatus {
+    /// Exists and can be opened for reading
+    #[default]
+    Ok,
+    /// Deleted (or a broken symlink)
+    Missing,
+    /// Exists but is a directory or other non-file
+    NotFile,
+    /// Exists but opening it failed, e.g. permission denied
+    Unreadable,
+}
+
+/// Result of `validate_paths`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct PathValidation {
+    /// The path as passed in
+    pub path: String,
+    pub status: PathStatus,
+    /// Why the file could not be opened, for `unreadable`
+    pub error: Option<String>,
+}
+
+/// Check that indexed files still exist and are readable, e.g. before showing
+/// search results, so dangling hits can be grayed out or reindexed. Relative
+/// paths are resolved against `root` when given. Results are in input order.
+#[napi(catch_unwind)]
+pub fn validate_paths(paths: Vec<String>, root: Option<String>) -> Vec<PathValidation> {
+    use rayon::prelude::*;
+
+    let root = root.map(std::path::PathBuf::from);
+    paths
+        .into_par_iter()
+        .map(|path| {
+            let full = match &root {
+                Some(root) => root.join(&path),
+                None => path.clone().into(),
+            };
+            let (status, error) = match fs::metadata(&full) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (PathStatus::Missing, None),
+                Err(e) => (PathStatus::Unreadable, Some(e.to_string())),
+                Ok(metadata) if !metadata.is_file() => (PathStatus::NotFile, None),
+                Ok(_) => match fs::File::open(&full) {
+                    Ok(_) => (PathStatus::Ok, None),
+                    Err(e) => (PathStatus::Unreadable, Some(e.to_string())),
+                },
+            };
+            PathValidation {
+                path,
+                status,
+                error,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,252 @@
+use crate::scanner::mtime_millis;
+use napi_derive::napi;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Mutex;
+
+/// Only the start of large files is inspected
+const SAMPLE_BYTES: u64 = 1024 * 1024;
+
+/// Indent widths considered when guessing the width of space indentation
+const INDENT_WIDTHS: [u32; 4] = [2, 3, 4, 8];
+
+/// Most styles kept; the least recently used is dropped beyond this
+const MAX_CACHED_STYLES: usize = 4096;
+
+/// Styles by path, valid while size and mtime are unchanged
+static STYLE_CACHE: Mutex<StyleCache> = Mutex::new(StyleCache::new());
+
+/// Least-recently-used cache of detected styles
+struct StyleCache {
+    entries: BTreeMap<String, CachedStyle>,
+    /// Bumped on every hit and insert, to order entries by last use
+    clock: u64,
+}
+
+struct CachedStyle {
+    size: u64,
+    mtime: f64,
+    style: FileStyle,
+    used: u64,
+}
+
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// No line breaks in the file
+    #[default]
+    None,
+    Lf,
+    Crlf,
+    /// Both LF and CRLF appear
+    Mixed,
+}
+
+#[napi(string_enum = "camelCase")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum IndentStyle {
+    /// No indented lines
+    #[default]
+    None,
+    Tabs,
+    Spaces,
+}
+
+/// Formatting conventions of a text file
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct FileStyle {
+    /// Dominant line ending; `mixed` when neither covers 90% of the breaks
+    pub line_ending: LineEnding,
+    /// Whether lines are mostly indented with tabs or spaces
+    pub indent: IndentStyle,
+    /// Spaces per indentation level, when `indent` is `spaces`
+    pub indent_width: Option<u32>,
+    /// Whether the file ends with a line break
+    pub final_newline: bool,
+}
+
+/// Detect line endings and indentation of a file from its first megabyte.
+/// Results are cached per path until the file's size or mtime changes; the
+/// 4096 most recently used are kept.
+#[napi(catch_unwind)]
+pub fn detect_file_style(file_path: String) -> napi::Result<FileStyle> {
+    let metadata = fs::metadata(&file_path).map_err(|e| {
+        napi::Error::from_reason(format!("Failed to stat {}: {}", file_path, e))
+    })?;
+    let (size, mtime) = (metadata.len(), mtime_millis(&metadata));
+    if let Some(style) = lock_cache().get(&file_path, size, mtime) {
+        return Ok(style);
+    }
+
+    let mut bytes = Vec::with_capacity(size.min(SAMPLE_BYTES) as usize);
+    fs::File::open(&file_path)
+        .and_then(|file| file.take(SAMPLE_BYTES).read_to_end(&mut bytes))
+        .map_err(|e| napi::Error::from_reason(format!("Failed to read {}: {}", file_path, e)))?;
+    let mut style = detect_style(&bytes);
+    if size > SAMPLE_BYTES {
+        // The sample's last byte says nothing about the end of the file
+        let mut file = fs::File::open(&file_path).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to read {}: {}", file_path, e))
+        })?;
+        let mut last = [0u8; 1];
+        style.final_newline = file
+            .seek(SeekFrom::End(-1))
+            .and_then(|_| file.read_exact(&mut last))
+            .is_ok_and(|_| last[0] == b'\n');
+    }
+
+    lock_cache().insert(file_path, size, mtime, style.clone());
+    Ok(style)
+}
+
+/// Drop all cached styles
+#[napi(catch_unwind)]
+pub fn clear_file_style_cache() {
+    lock_cache().clear();
+}
+
+fn lock_cache() -> std::sync::MutexGuard<'static, StyleCache> {
+    STYLE_CACHE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl StyleCache {
+    const fn new() -> Self {
+        StyleCache {
+            entries: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// The style cached for `path` if it was detected at this size and mtime
+    fn get(&mut self, path: &str, size: u64, mtime: f64) -> Option<FileStyle> {
+        let entry = self.entries.get_mut(path)?;
+        if entry.size != size || entry.mtime != mtime {
+            return None;
+        }
+        self.clock += 1;
+        entry.used = self.clock;
+        Some(entry.style.clone())
+    }
+
+    fn insert(&mut self, path: String, size: u64, mtime: f64, style: FileStyle) {
+        self.clock += 1;
+        let entry = CachedStyle {
+            size,
+            mtime,
+            style,
+            used: self.clock,
+        };
+        self.entries.insert(path, entry);
+        if self.entries.len() > MAX_CACHED_STYLES {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+fn detect_style(bytes: &[u8]) -> FileStyle {
+    let breaks = bytes.iter().filter(|&&b| b == b'\n').count();
+    let crlf = bytes.windows(2).filter(|w| w == b"\r\n").count();
+    let lf = breaks - crlf;
+
+    let mut tab_lines = 0usize;
+    let mut space_lines = 0usize;
+    let mut width_votes = [0usize; INDENT_WIDTHS.len()];
+    let mut previous_indent = 0usize;
+
+    for line in bytes.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        // Blank lines carry no indentation information
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            continue;
+        }
+        let indent = &line[..line.iter().take_while(|&&b| b == b' ' || b == b'\t').count()];
+        if indent.first() == Some(&b'\t') {
+            tab_lines += 1;
+            previous_indent = 0;
+            continue;
+        }
+        let spaces = indent.len();
+        // The odd space before `*` in block comments is alignment, not indentation
+        if spaces % 2 == 1 && line.get(spaces) == Some(&b'*') {
+            continue;
+        }
+        if spaces > 0 {
+            space_lines += 1;
+        }
+        if spaces > previous_indent {
+            let step = (spaces - previous_indent) as u32;
+            for (votes, width) in width_votes.iter_mut().zip(INDENT_WIDTHS) {
+                if step == width {
+                    *votes += 1;
+                }
+            }
+        }
+        previous_indent = spaces;
+    }
+
+    let line_ending = match (lf, crlf) {
+        (0, 0) => LineEnding::None,
+        (_, 0) => LineEnding::Lf,
+        (0, _) => LineEnding::Crlf,
+        (lf, crlf) if lf * 10 >= (lf + crlf) * 9 => LineEnding::Lf,
+        (lf, crlf) if crlf * 10 >= (lf + crlf) * 9 => LineEnding::Crlf,
+        _ => LineEnding::Mixed,
+    };
+    let indent = match (tab_lines, space_lines) {
+        (0, 0) => IndentStyle::None,
+        (tabs, spaces) if tabs > spaces => IndentStyle::Tabs,
+        _ => IndentStyle::Spaces,
+    };
+    let indent_width = (indent == IndentStyle::Spaces)
+        .then(|| {
+            INDENT_WIDTHS
+                .iter()
+                .zip(width_votes)
+                .filter(|(_, votes)| *votes > 0)
+                .max_by_key(|(_, votes)| *votes)
+                .map(|(width, _)| *width)
+        })
+        .flatten();
+
+    FileStyle {
+        line_ending,
+        indent,
+        indent_width,
+        final_newline: bytes.last() == Some(&b'\n'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn style_cache_drops_least_recently_used() {
+        let mut cache = StyleCache::new();
+        for i in 0..MAX_CACHED_STYLES {
+            cache.insert(format!("f{}", i), 1, 1.0, detect_style(b"a\n"));
+        }
+        assert!(cache.get("f0", 1, 1.0).is_some());
+        assert!(cache.get("f1", 2, 1.0).is_none());
+
+        cache.insert("new".to_string(), 1, 1.0, detect_style(b"a\n"));
+        assert_eq!(cache.entries.len(), MAX_CACHED_STYLES);
+        assert!(cache.get("f0", 1, 1.0).is_some());
+        assert!(cache.get("f1", 1, 1.0).is_none());
+        assert!(cache.get("f2", 1, 1.0).is_some());
+        assert!(cache.get("new", 1, 1.0).is_some());
+    }
+}
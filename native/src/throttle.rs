@@ -0,0 +1,37 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Shared byte-rate limiter for batch reads.
+///
+/// Each `acquire` reserves the next `bytes / rate` slice of time and sleeps until
+/// that slice begins, so concurrent readers are serialized onto one budget.
+pub(crate) struct Throttle {
+    bytes_per_second: f64,
+    next_free: Mutex<Instant>,
+}
+
+impl Throttle {
+    /// `None` for a non-positive rate, which means unlimited
+    pub(crate) fn new(bytes_per_second: i64) -> Option<Self> {
+        (bytes_per_second > 0).then(|| Throttle {
+            bytes_per_second: bytes_per_second as f64,
+            next_free: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Block until `bytes` more may be read without exceeding the rate
+    pub(crate) fn acquire(&self, bytes: u64) {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second);
+        let start = {
+            let mut next_free = self.next_free.lock().unwrap_or_else(|e| e.into_inner());
+            let start = (*next_free).max(Instant::now());
+            *next_free = start + cost;
+            start
+        };
+        let wait = start.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::hasher::HashAlgorithm;
+use crate::merkle::{
+    build_merkle_tree, diff_nodes, root_node, tree_algorithm, tree_namespace, tree_scheme,
+    DiffOptions, FileHashEntry, MerkleDiff, MerkleNode, MerkleOptions, NodeIndex, TreeChange,
+};
+use crate::treefile::{deserialize_tree, serialize_nodes};
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+
+/// A Merkle tree held natively. JS only keeps a handle, so lookups, diffs and
+/// updates do not marshal the whole node list on every call.
+#[napi]
+pub struct MerkleTree {
+    /// Indexed by path, so updates rehash only the changed paths' ancestors
+    tree: NodeIndex,
+    algorithm: HashAlgorithm,
+    scheme: u32,
+    namespace: Option<String>,
+    /// Path of the root node when the tree was created
+    root: Option<String>,
+}
+
+#[napi]
+impl MerkleTree {
+    /// Take over a node list, e.g. from `build_merkle_tree`. Fails if the nodes
+    /// mix algorithms, hash schemes or namespaces.
+    #[napi(constructor, catch_unwind)]
+    pub fn new(nodes: Vec<MerkleNode>) -> napi::Result<Self> {
+        Ok(MerkleTree {
+            algorithm: tree_algorithm(&nodes)?,
+            scheme: tree_scheme(&nodes)?,
+            namespace: tree_namespace(&nodes)?,
+            root: root_node(&nodes).map(|n| n.path.clone()),
+            tree: NodeIndex::new(nodes),
+        })
+    }
+
+    /// Like `build_merkle_tree`, keeping the result native
+    #[napi(factory, catch_unwind)]
+    pub fn build(
+        file_hashes: Vec<FileHashEntry>,
+        options: Option<MerkleOptions>,
+    ) -> napi::Result<Self> {
+        MerkleTree::new(build_merkle_tree(file_hashes, options)?)
+    }
+
+    /// Load a tree written by `serialize`, `serialize_tree` or `save_tree`
+    #[napi(factory, catch_unwind)]
+    pub fn deserialize(data: Buffer) -> napi::Result<Self> {
+        MerkleTree::new(deserialize_tree(data)?)
+    }
+
+    /// Number of nodes
+    #[napi(getter, catch_unwind)]
+    pub fn node_count(&self) -> u32 {
+        self.tree.nodes().len() as u32
+    }
+
+    /// Root hash, like `get_root_hash`
+    #[napi(catch_unwind)]
+    pub fn root_hash(&self) -> Option<String> {
+        self.root
+            .as_deref()
+            .and_then(|root| self.tree.get(root))
+            .or_else(|| self.tree.get("."))
+            .map(|n| n.hash.clone())
+    }
+
+    /// The node at `path`, or None
+    #[napi(catch_unwind)]
+    pub fn get_node(&self, path: String) -> Option<MerkleNode> {
+        self.tree.get(&path).cloned()
+    }
+
+    /// The child nodes of the directory at `path`, sorted by path; empty for
+    /// files and unknown paths
+    #[napi(catch_unwind)]
+    pub fn children(&self, path: String) -> Vec<MerkleNode> {
+        let Some(node) = self.tree.get(&path) else {
+            return vec![];
+        };
+        let mut children: Vec<MerkleNode> = node
+            .children
+            .iter()
+            .filter_map(|c| self.tree.get(c).cloned())
+            .collect();
+        children.sort_by(|a, b| a.path.cmp(&b.path));
+        children
+    }
+
+    /// Diff against `other`, like `diff_merkle_trees` with this tree as the old side
+    #[napi(catch_unwind)]
+    pub fn diff(
+        &self,
+        other: &MerkleTree,
+        options: Option<DiffOptions>,
+    ) -> napi::Result<MerkleDiff> {
+        diff_nodes(self.tree.nodes(), other.tree.nodes(), options)
+    }
+
+    /// Apply file changes in place, like `apply_changes`, and return the new root
+    /// hash. Only the changed nodes and their ancestors are touched. The tree is
+    /// left unchanged when a change is rejected.
+    #[napi(catch_unwind)]
+    pub fn apply_changes(&mut self, changes: Vec<TreeChange>) -> napi::Result<Option<String>> {
+        self.tree.apply(changes, self.algorithm, self.scheme, &self.namespace)?;
+        Ok(self.tree.get(".").map(|n| n.hash.clone()))
+    }
+
+    /// The tree in the `serialize_tree` format
+    #[napi(catch_unwind)]
+    pub fn serialize(&self) -> napi::Result<Buffer> {
+        serialize_nodes(self.tree.nodes())
+    }
+
+    /// Every node, sorted by path
+    #[napi(catch_unwind)]
+    pub fn to_nodes(&self) -> Vec<MerkleNode> {
+        let mut nodes = self.tree.nodes().to_vec();
+        nodes.sort_by(|a, b| a.path.cmp(&b.path));
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::{apply_changes, NAMED_HASH_SCHEME};
+
+    fn build(files: &[(&str, &str)]) -> Vec<MerkleNode> {
+        let files = files
+            .iter()
+            .map(|(path, hash)| FileHashEntry {
+                path: path.to_string(),
+                hash: hash.to_string(),
+                is_directory: None,
+                size: Some(1),
+            })
+            .collect();
+        let options = MerkleOptions {
+            hash_scheme: Some(NAMED_HASH_SCHEME),
+            ..Default::default()
+        };
+        build_merkle_tree(files, Some(options)).unwrap()
+    }
+
+    fn change(path: &str, hash: Option<&str>) -> TreeChange {
+        TreeChange {
+            path: path.to_string(),
+            hash: hash.map(str::to_string),
+            deleted: hash.is_none().then_some(true),
+            is_directory: None,
+            size: Some(1),
+        }
+    }
+
+    fn sorted(mut nodes: Vec<MerkleNode>) -> Vec<(String, String, Vec<String>)> {
+        nodes.sort_by(|a, b| a.path.cmp(&b.path));
+        nodes
+            .into_iter()
+            .map(|mut n| {
+                n.children.sort();
+                (n.path, n.hash, n.children)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn apply_changes_updates_in_place_and_rolls_back_rejected_batches() {
+        let files = [("a/x.ts", "h1"), ("a/y.ts", "h2"), ("b/z.ts", "h3")];
+        let mut tree = MerkleTree::new(build(&files)).unwrap();
+        let changes = || vec![change("a/x.ts", Some("h4")), change("b/z.ts", None)];
+
+        let root = tree.apply_changes(changes()).unwrap();
+        let expected = apply_changes(build(&files), changes()).unwrap();
+        assert_eq!(root, expected.root_hash);
+        assert_eq!(tree.root_hash(), expected.root_hash);
+        assert_eq!(sorted(tree.to_nodes()), sorted(expected.nodes));
+        assert!(tree.get_node("b".to_string()).is_none());
+
+        let before = sorted(tree.to_nodes());
+        let rejected = vec![
+            change("a/y.ts", None),
+            change("c/new.ts", Some("h5")),
+            change("a", Some("h6")),
+        ];
+        assert!(tree.apply_changes(rejected).is_err());
+        assert_eq!(sorted(tree.to_nodes()), before);
+        assert_eq!(tree.root_hash(), root);
+        assert_eq!(tree.children("a".to_string()).len(), 2);
+    }
+}
@@ -0,0 +1,933 @@
+use crate::cache::{push_varint, write_atomic, Reader};
+use crate::hasher::HashAlgorithm;
+use crate::merkle::{
+    check_scheme, diff_merkle_trees, diff_nodes, parent_path, tree_algorithm, tree_namespace,
+    tree_scheme, DiffOptions, MerkleDiff, MerkleNode, LEGACY_HASH_SCHEME, NAMED_HASH_SCHEME,
+};
+use memmap2::Mmap;
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// File magic and format versions of serialized trees; version 2 adds the hash
+/// scheme and is only written for trees not using the legacy scheme, version 3
+/// adds the namespace and is only written for namespaced trees, version 4 adds
+/// file sizes and is only written for trees with at least one
+const MAGIC: &[u8; 4] = b"CNMT";
+const FORMAT_VERSION: u8 = 1;
+const FORMAT_VERSION_SCHEME: u8 = 2;
+const FORMAT_VERSION_NAMESPACE: u8 = 3;
+const FORMAT_VERSION_SIZES: u8 = 4;
+
+const FLAG_FILE: u8 = 1;
+const FLAG_ROOT: u8 = 2;
+/// A varint file size follows the hash (version 4)
+const FLAG_SIZE: u8 = 4;
+/// `hash_len` value marking hashes stored as length-prefixed strings
+const STRING_HASHES: u8 = 0;
+
+/// Version of the canonical JSON schema
+const JSON_VERSION: u32 = 1;
+
+/// Canonical JSON document; fields are declared in sorted order so serde emits sorted keys
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TreeJson {
+    algorithm: String,
+    /// Omitted for the legacy scheme, so those documents are unchanged
+    #[serde(
+        rename = "hashScheme",
+        default = "legacy_scheme",
+        skip_serializing_if = "is_legacy_scheme"
+    )]
+    hash_scheme: u32,
+    /// Omitted for trees without a namespace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    nodes: Vec<NodeJson>,
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct NodeJson {
+    children: Vec<String>,
+    hash: String,
+    is_file: bool,
+    path: String,
+    /// File size in bytes; omitted for directories and files without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<i64>,
+}
+
+/// Serialize a Merkle tree into a compact binary layout for fast loading.
+///
+/// Nodes are written in path order; children are not stored but rebuilt from
+/// the paths, and directory sizes and file counts are summed from the files on
+/// load. Flags are 1 for files, 2 for the root and 4 for files with a size.
+/// Layout, fixed-width integers little-endian:
+///
+/// ```text
+/// "CNMT" u8:version u8:algorithm [u8:hash_scheme [u8:has_namespace]
+///     [varint:namespace_len namespace]] u8:hash_len u32:count
+/// count x { varint:shared_prefix varint:suffix_len suffix u8:flags hash [varint:size] }
+/// ```
+///
+/// `hash_scheme` is present from version 2; the namespace is always present in
+/// version 3 and follows `has_namespace` = 1 in version 4. Version 1 files use
+/// scheme 1, and only version 4 stores sizes.
+///
+/// When every hash is lowercase hex of one length, `hash_len` is the digest size
+/// and hashes are stored as raw bytes; otherwise it is 0 and each hash is a
+/// varint length followed by the string.
+#[napi(catch_unwind)]
+pub fn serialize_tree(nodes: Vec<MerkleNode>) -> napi::Result<Buffer> {
+    serialize_nodes(&nodes)
+}
+
+/// `serialize_tree` over borrowed nodes
+pub(crate) fn serialize_nodes(nodes: &[MerkleNode]) -> napi::Result<Buffer> {
+    let algorithm = tree_algorithm(nodes)?;
+    let scheme = tree_scheme(nodes)?;
+    let namespace = tree_namespace(nodes)?;
+    let mut sorted: Vec<&MerkleNode> = nodes.iter().collect();
+    sorted.sort_by(|a, b| a.path.cmp(&b.path));
+    sorted.dedup_by(|a, b| a.path == b.path);
+
+    let has_sizes = sorted.iter().any(|n| n.is_file && n.size.is_some());
+
+    let hash_len = raw_hash_len(&sorted);
+    let mut out = Vec::with_capacity(16 + sorted.len() * (24 + hash_len as usize));
+    out.extend_from_slice(MAGIC);
+    if has_sizes {
+        out.push(FORMAT_VERSION_SIZES);
+        out.push(algorithm_code(algorithm));
+        out.push(scheme as u8);
+        out.push(u8::from(namespace.is_some()));
+        if let Some(namespace) = &namespace {
+            push_varint(&mut out, namespace.len() as u64);
+            out.extend_from_slice(namespace.as_bytes());
+        }
+    } else if let Some(namespace) = &namespace {
+        out.push(FORMAT_VERSION_NAMESPACE);
+        out.push(algorithm_code(algorithm));
+        out.push(scheme as u8);
+        push_varint(&mut out, namespace.len() as u64);
+        out.extend_from_slice(namespace.as_bytes());
+    } else if scheme == LEGACY_HASH_SCHEME {
+        out.push(FORMAT_VERSION);
+        out.push(algorithm_code(algorithm));
+    } else {
+        out.push(FORMAT_VERSION_SCHEME);
+        out.push(algorithm_code(algorithm));
+        out.push(scheme as u8);
+    }
+    out.push(hash_len);
+    out.extend_from_slice(&(sorted.len() as u32).to_le_bytes());
+
+    let mut previous = "";
+    for node in sorted {
+        let shared = common_prefix(previous, &node.path);
+        let suffix = &node.path.as_bytes()[shared..];
+        push_varint(&mut out, shared as u64);
+        push_varint(&mut out, suffix.len() as u64);
+        out.extend_from_slice(suffix);
+        let mut flags = 0;
+        if node.is_file {
+            flags |= FLAG_FILE;
+        }
+        if node.is_root == Some(true) {
+            flags |= FLAG_ROOT;
+        }
+        let size = node.size.filter(|_| node.is_file);
+        if size.is_some() {
+            flags |= FLAG_SIZE;
+        }
+        out.push(flags);
+        if hash_len == STRING_HASHES {
+            push_varint(&mut out, node.hash.len() as u64);
+            out.extend_from_slice(node.hash.as_bytes());
+        } else {
+            // raw_hash_len checked that every hash decodes
+            out.extend_from_slice(&hex::decode(&node.hash).unwrap_or_default());
+        }
+        if let Some(size) = size {
+            push_varint(&mut out, size as u64);
+        }
+        previous = &node.path;
+    }
+    Ok(out.into())
+}
+
+/// Read a tree written by `serialize_tree`
+#[napi(catch_unwind)]
+pub fn deserialize_tree(data: Buffer) -> napi::Result<Vec<MerkleNode>> {
+    decode(&data).ok_or_else(|| napi::Error::from_reason("Invalid or corrupt serialized tree"))
+}
+
+fn decode(bytes: &[u8]) -> Option<Vec<MerkleNode>> {
+    let (header, records) = parse(bytes)?;
+    Some(materialize(bytes, &header, &records))
+}
+
+/// Header fields of a serialized tree
+struct Header {
+    algorithm: HashAlgorithm,
+    scheme: u32,
+    namespace: Option<String>,
+    hash_len: u8,
+}
+
+/// One node of a serialized tree, with its hash left in place
+struct Record {
+    path: String,
+    flags: u8,
+    hash: Range<usize>,
+    /// Stored for files, summed from the files below for directories
+    size: Option<i64>,
+    file_count: u32,
+}
+
+/// Validate a serialized tree and index its records, in path order
+fn parse(bytes: &[u8]) -> Option<(Header, Vec<Record>)> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(4)? != MAGIC {
+        return None;
+    }
+    let version = reader.u8()?;
+    let algorithm = algorithm_from_code(reader.u8()?)?;
+    let scheme = match version {
+        FORMAT_VERSION => LEGACY_HASH_SCHEME,
+        FORMAT_VERSION_SCHEME | FORMAT_VERSION_NAMESPACE | FORMAT_VERSION_SIZES => {
+            reader.u8()? as u32
+        }
+        _ => return None,
+    };
+    check_scheme(scheme).ok()?;
+    let has_namespace = match version {
+        FORMAT_VERSION_NAMESPACE => true,
+        FORMAT_VERSION_SIZES => match reader.u8()? {
+            0 => false,
+            1 => true,
+            _ => return None,
+        },
+        _ => false,
+    };
+    let namespace = if has_namespace {
+        let len = reader.varint()? as usize;
+        Some(std::str::from_utf8(reader.take(len)?).ok()?.to_string())
+    } else {
+        None
+    };
+    let hash_len = reader.u8()?;
+    let count = reader.u32()? as usize;
+
+    let mut records: Vec<Record> = Vec::with_capacity(count.min(bytes.len()));
+    for _ in 0..count {
+        let previous = records.last().map_or("", |r| r.path.as_str());
+        let shared = reader.varint()? as usize;
+        let suffix_len = reader.varint()? as usize;
+        let mut path = previous.get(..shared)?.to_string();
+        path.push_str(std::str::from_utf8(reader.take(suffix_len)?).ok()?);
+        if path.as_str() <= previous && !records.is_empty() {
+            return None;
+        }
+        let flags = reader.u8()?;
+        if flags & FLAG_SIZE != 0 && (version != FORMAT_VERSION_SIZES || flags & FLAG_FILE == 0) {
+            return None;
+        }
+        let len = if hash_len == STRING_HASHES {
+            reader.varint()? as usize
+        } else {
+            hash_len as usize
+        };
+        let start = reader.pos;
+        let hash = reader.take(len)?;
+        if hash_len == STRING_HASHES {
+            std::str::from_utf8(hash).ok()?;
+        }
+        let hash = start..reader.pos;
+        let size = if flags & FLAG_SIZE != 0 {
+            Some(reader.varint()? as i64)
+        } else {
+            None
+        };
+        records.push(Record {
+            path,
+            flags,
+            hash,
+            size,
+            file_count: u32::from(flags & FLAG_FILE != 0),
+        });
+    }
+    if reader.pos != bytes.len() {
+        return None;
+    }
+    let entries: Vec<_> = records
+        .iter()
+        .map(|r| (r.path.as_str(), r.flags & FLAG_FILE != 0, r.size))
+        .collect();
+    let totals = sum_directories(&entries);
+    for (record, (size, file_count)) in records.iter_mut().zip(totals) {
+        record.size = size;
+        record.file_count = file_count;
+    }
+    let header = Header {
+        algorithm,
+        scheme,
+        namespace,
+        hash_len,
+    };
+    Some((header, records))
+}
+
+/// Hash of a parsed record (validated by `parse`)
+fn record_hash(bytes: &[u8], header: &Header, record: &Record) -> String {
+    let hash = &bytes[record.hash.clone()];
+    if header.hash_len == STRING_HASHES {
+        String::from_utf8_lossy(hash).into_owned()
+    } else {
+        hex::encode(hash)
+    }
+}
+
+/// A parsed record as a node, without children
+fn record_node(bytes: &[u8], header: &Header, record: &Record) -> MerkleNode {
+    MerkleNode {
+        path: record.path.clone(),
+        hash: record_hash(bytes, header, record),
+        is_file: record.flags & FLAG_FILE != 0,
+        children: vec![],
+        algorithm: Some(header.algorithm),
+        is_root: (record.flags & FLAG_ROOT != 0).then_some(true),
+        size: record.size,
+        file_count: Some(record.file_count),
+        hash_scheme: Some(header.scheme),
+        namespace: header.namespace.clone(),
+    }
+}
+
+/// All records as nodes, with children rebuilt from the paths
+fn materialize(bytes: &[u8], header: &Header, records: &[Record]) -> Vec<MerkleNode> {
+    let mut nodes: BTreeMap<String, MerkleNode> = records
+        .iter()
+        .map(|r| (r.path.clone(), record_node(bytes, header, r)))
+        .collect();
+    for record in records.iter().filter(|r| r.path != ".") {
+        if let Some(parent) = nodes.get_mut(&parent_path(&record.path)) {
+            parent.children.push(record.path.clone());
+        }
+    }
+    nodes.into_values().collect()
+}
+
+/// Size and file count of each node, given as (path, is_file, size) sorted by
+/// path: files keep their size and count 1, directories total the files below.
+/// These are the totals `build_merkle_tree` computes, so loaded trees match built ones.
+fn sum_directories(entries: &[(&str, bool, Option<i64>)]) -> Vec<(Option<i64>, u32)> {
+    let mut totals: Vec<(Option<i64>, u32)> = entries
+        .iter()
+        .map(|&(_, is_file, size)| if is_file { (size, 1) } else { (None, 0) })
+        .collect();
+    // Descendants sort after their directories, so walking backwards finishes
+    // every directory before its total is added to its parent
+    for (i, &(path, _, _)) in entries.iter().enumerate().rev() {
+        if path == "." {
+            continue;
+        }
+        let parent = parent_path(path);
+        let Ok(p) = entries.binary_search_by(|e| e.0.cmp(parent.as_str())) else {
+            continue;
+        };
+        if p == i || entries[p].1 {
+            continue;
+        }
+        let (size, file_count) = totals[i];
+        if let Some(size) = size {
+            *totals[p].0.get_or_insert(0) += size;
+        }
+        totals[p].1 += file_count;
+    }
+    totals
+}
+
+/// Write a tree to `file_path` in the `serialize_tree` format, for `open_tree`.
+/// The file is replaced atomically, so trees opened from it stay intact.
+#[napi(catch_unwind)]
+pub fn save_tree(file_path: String, nodes: Vec<MerkleNode>) -> napi::Result<()> {
+    let bytes = serialize_tree(nodes)?;
+    write_atomic(Path::new(&file_path), &bytes)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to write {}: {}", file_path, e)))
+}
+
+/// A tree saved with `save_tree`, memory-mapped and queried in place so
+/// startup does not turn every node into a JS object
+#[napi]
+pub struct MappedTree {
+    map: Mmap,
+    header: Header,
+    records: Vec<Record>,
+}
+
+/// Map a tree written by `save_tree` (or any `serialize_tree` output)
+#[napi(catch_unwind)]
+pub fn open_tree(file_path: String) -> napi::Result<MappedTree> {
+    let file = fs::File::open(&file_path)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to open {}: {}", file_path, e)))?;
+    // SAFETY: read-only map. `save_tree` replaces files by rename, so the mapped
+    // inode is never rewritten; truncation by another writer can still fault.
+    let map = unsafe { Mmap::map(&file) }
+        .map_err(|e| napi::Error::from_reason(format!("Failed to map {}: {}", file_path, e)))?;
+    let (header, records) = parse(&map).ok_or_else(|| {
+        napi::Error::from_reason(format!("Invalid or corrupt tree file {}", file_path))
+    })?;
+    Ok(MappedTree {
+        map,
+        header,
+        records,
+    })
+}
+
+#[napi]
+impl MappedTree {
+    /// Number of nodes
+    #[napi(getter, catch_unwind)]
+    pub fn node_count(&self) -> u32 {
+        self.records.len() as u32
+    }
+
+    /// Root hash, like `get_root_hash`
+    #[napi(catch_unwind)]
+    pub fn root_hash(&self) -> Option<String> {
+        self.records
+            .iter()
+            .find(|r| r.flags & FLAG_ROOT != 0)
+            .or_else(|| self.find(".").map(|i| &self.records[i]))
+            .or_else(|| self.find("").map(|i| &self.records[i]))
+            .map(|r| record_hash(&self.map, &self.header, r))
+    }
+
+    /// The node at `path` with its children, or None
+    #[napi(catch_unwind)]
+    pub fn get(&self, path: String) -> Option<MerkleNode> {
+        let index = self.find(&path)?;
+        let mut node = record_node(&self.map, &self.header, &self.records[index]);
+        if !node.is_file {
+            node.children = self.children(&path);
+        }
+        Some(node)
+    }
+
+    /// Hash of the node at `path`, or None
+    #[napi(catch_unwind)]
+    pub fn hash_of(&self, path: String) -> Option<String> {
+        let index = self.find(&path)?;
+        Some(record_hash(&self.map, &self.header, &self.records[index]))
+    }
+
+    /// Diff against a newer tree, like `diff_merkle_trees` with this tree as the
+    /// old side. Under hash scheme 2 both trees are walked from the root and only
+    /// directories whose hashes differ are entered, so unchanged subtrees are
+    /// never materialized; scheme 1 hashes do not cover file names, so every
+    /// node is compared.
+    #[napi(catch_unwind)]
+    pub fn diff(
+        &self,
+        new_nodes: Vec<MerkleNode>,
+        options: Option<DiffOptions>,
+    ) -> napi::Result<MerkleDiff> {
+        if self.header.scheme != NAMED_HASH_SCHEME {
+            return diff_merkle_trees(self.to_nodes(), new_nodes, options);
+        }
+        let mut sorted: Vec<&MerkleNode> = new_nodes.iter().collect();
+        sorted.sort_by(|a, b| a.path.cmp(&b.path));
+        match self.differing_nodes(&sorted) {
+            Some((old_nodes, new_nodes)) => diff_nodes(&old_nodes, &new_nodes, options),
+            None => diff_nodes(&self.to_nodes(), &new_nodes, options),
+        }
+    }
+
+    /// Every node, as `deserialize_tree` returns them
+    #[napi(catch_unwind)]
+    pub fn to_nodes(&self) -> Vec<MerkleNode> {
+        materialize(&self.map, &self.header, &self.records)
+    }
+}
+
+impl MappedTree {
+    fn find(&self, path: &str) -> Option<usize> {
+        self.records
+            .binary_search_by(|r| r.path.as_str().cmp(path))
+            .ok()
+    }
+
+    /// Paths directly below `dir`; they sit in one contiguous, sorted run
+    fn children(&self, dir: &str) -> Vec<String> {
+        let prefix = child_prefix(dir);
+        self.records[below(&self.records, |r| &r.path, &prefix)]
+            .iter()
+            .filter(|r| is_child(&r.path, &prefix))
+            .map(|r| r.path.clone())
+            .collect()
+    }
+
+    /// The nodes of both trees that can contribute to their diff: the roots, and
+    /// below them everything except subtrees whose (scheme 2) hashes match on
+    /// both sides. `new_nodes` is sorted by path. None if either tree has no
+    /// directory at ".", as the walk starts there.
+    fn differing_nodes(
+        &self,
+        new_nodes: &[&MerkleNode],
+    ) -> Option<(Vec<MerkleNode>, Vec<MerkleNode>)> {
+        let find_new = |path: &str| new_nodes.binary_search_by(|n| n.path.as_str().cmp(path));
+        let old_root = self.find(".")?;
+        let new_root = find_new(".").ok()?;
+        if self.records[old_root].flags & FLAG_FILE != 0 || new_nodes[new_root].is_file {
+            return None;
+        }
+
+        let mut old_kept = vec![old_root];
+        let mut new_kept = vec![new_root];
+        let mut pending = Vec::new();
+        if self.hash_of(".".to_string()).as_deref() != Some(new_nodes[new_root].hash.as_str()) {
+            pending.push(".".to_string());
+        }
+        while let Some(dir) = pending.pop() {
+            let prefix = child_prefix(&dir);
+            for i in below(&self.records, |r| &r.path, &prefix) {
+                let record = &self.records[i];
+                if !is_child(&record.path, &prefix) {
+                    continue;
+                }
+                let is_file = record.flags & FLAG_FILE != 0;
+                match find_new(&record.path) {
+                    Ok(j) if new_nodes[j].is_file == is_file => {
+                        if record_hash(&self.map, &self.header, record) != new_nodes[j].hash {
+                            old_kept.push(i);
+                            new_kept.push(j);
+                            if !is_file {
+                                pending.push(record.path.clone());
+                            }
+                        }
+                    }
+                    _ => {
+                        let subtree = child_prefix(&record.path);
+                        old_kept.push(i);
+                        old_kept.extend(below(&self.records, |r| &r.path, &subtree));
+                    }
+                }
+            }
+            for j in below(new_nodes, |n| &n.path, &prefix) {
+                let node = new_nodes[j];
+                if !is_child(&node.path, &prefix) {
+                    continue;
+                }
+                let same_kind = self
+                    .find(&node.path)
+                    .is_some_and(|i| (self.records[i].flags & FLAG_FILE != 0) == node.is_file);
+                if !same_kind {
+                    let subtree = child_prefix(&node.path);
+                    new_kept.push(j);
+                    new_kept.extend(below(new_nodes, |n| &n.path, &subtree));
+                }
+            }
+        }
+
+        old_kept.sort_unstable();
+        old_kept.dedup();
+        new_kept.sort_unstable();
+        new_kept.dedup();
+        let old_nodes = old_kept
+            .into_iter()
+            .map(|i| record_node(&self.map, &self.header, &self.records[i]))
+            .collect();
+        let new_nodes = new_kept.into_iter().map(|j| new_nodes[j].clone()).collect();
+        Some((old_nodes, new_nodes))
+    }
+}
+
+/// Prefix shared by the paths below `dir`
+fn child_prefix(dir: &str) -> String {
+    if dir == "." {
+        String::new()
+    } else {
+        format!("{}/", dir)
+    }
+}
+
+/// Whether `path` sits directly below the directory with `prefix`
+fn is_child(path: &str, prefix: &str) -> bool {
+    path != "." && !path[prefix.len()..].contains('/')
+}
+
+/// Positions of the items whose path starts with `prefix`, in a slice sorted by
+/// path; they form one contiguous run
+fn below<T>(items: &[T], path: impl Fn(&T) -> &String, prefix: &str) -> Range<usize> {
+    let start = items.partition_point(|item| path(item).as_str() < prefix);
+    let len = items[start..]
+        .iter()
+        .take_while(|item| path(item).starts_with(prefix))
+        .count();
+    start..start + len
+}
+
+/// Export a tree as canonical JSON, comparable byte for byte across implementations:
+///
+/// ```text
+/// {"algorithm":"sha256","nodes":[{"children":[..],"hash":"..","isFile":false,"path":"."},..],"version":1}
+/// ```
+///
+/// Keys are sorted, nodes are sorted by path, children are sorted, there is no
+/// whitespace, and strings are escaped as `JSON.stringify` does. Absent node
+/// algorithms are written as `sha256`. Trees using a hash scheme other than 1
+/// carry a `"hashScheme"` key after `"algorithm"`, and namespaced trees a
+/// `"namespace"` key before `"nodes"`. Files with a size carry a `"size"` key
+/// after `"path"`; directory sizes and file counts are summed again on import.
+#[napi(catch_unwind)]
+pub fn export_tree_json(nodes: Vec<MerkleNode>) -> napi::Result<String> {
+    let algorithm = tree_algorithm(&nodes)?;
+    let hash_scheme = tree_scheme(&nodes)?;
+    let namespace = tree_namespace(&nodes)?;
+    let mut json_nodes: Vec<NodeJson> = nodes
+        .into_iter()
+        .map(|node| {
+            let mut children = node.children;
+            children.sort();
+            NodeJson {
+                children,
+                hash: node.hash,
+                is_file: node.is_file,
+                path: node.path,
+                size: node.size.filter(|_| node.is_file),
+            }
+        })
+        .collect();
+    json_nodes.sort_by(|a, b| a.path.cmp(&b.path));
+    json_nodes.dedup_by(|a, b| a.path == b.path);
+
+    let document = TreeJson {
+        algorithm: algorithm_name(algorithm).to_string(),
+        hash_scheme,
+        namespace,
+        nodes: json_nodes,
+        version: JSON_VERSION,
+    };
+    serde_json::to_string(&document)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to export tree: {}", e)))
+}
+
+/// Import a tree from the canonical JSON produced by `export_tree_json`.
+/// The node at "." is marked as the root.
+#[napi(catch_unwind)]
+pub fn import_tree_json(json: String) -> napi::Result<Vec<MerkleNode>> {
+    let document: TreeJson = serde_json::from_str(&json)
+        .map_err(|e| napi::Error::from_reason(format!("Invalid tree JSON: {}", e)))?;
+    if document.version != JSON_VERSION {
+        return Err(napi::Error::from_reason(format!(
+            "Unsupported tree JSON version {} (expected {})",
+            document.version, JSON_VERSION
+        )));
+    }
+    let algorithm = algorithm_from_name(&document.algorithm).ok_or_else(|| {
+        napi::Error::from_reason(format!("Unknown algorithm {}", document.algorithm))
+    })?;
+    let mut json_nodes = document.nodes;
+    json_nodes.sort_by(|a, b| a.path.cmp(&b.path));
+    let entries: Vec<_> = json_nodes
+        .iter()
+        .map(|n| (n.path.as_str(), n.is_file, n.size.filter(|_| n.is_file)))
+        .collect();
+    let totals = sum_directories(&entries);
+    let nodes: Vec<MerkleNode> = json_nodes
+        .into_iter()
+        .zip(totals)
+        .map(|(node, (size, file_count))| MerkleNode {
+            hash: node.hash,
+            is_file: node.is_file,
+            children: node.children,
+            algorithm: Some(algorithm),
+            is_root: (node.path == ".").then_some(true),
+            size,
+            file_count: Some(file_count),
+            hash_scheme: Some(document.hash_scheme),
+            namespace: document.namespace.clone(),
+            path: node.path,
+        })
+        .collect();
+    tree_scheme(&nodes)?;
+    Ok(nodes)
+}
+
+fn legacy_scheme() -> u32 {
+    LEGACY_HASH_SCHEME
+}
+
+fn is_legacy_scheme(scheme: &u32) -> bool {
+    *scheme == LEGACY_HASH_SCHEME
+}
+
+/// Digest size if every hash is lowercase hex of the same even length
+/// (1..=255 bytes), else `STRING_HASHES`
+fn raw_hash_len(nodes: &[&MerkleNode]) -> u8 {
+    let Some(first) = nodes.first() else {
+        return STRING_HASHES;
+    };
+    let len = first.hash.len();
+    let is_raw = len % 2 == 0
+        && (2..=510).contains(&len)
+        && nodes.iter().all(|n| {
+            n.hash.len() == len
+                && n
+                    .hash
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        });
+    if is_raw {
+        (len / 2) as u8
+    } else {
+        STRING_HASHES
+    }
+}
+
+/// Length in bytes of the common prefix, on a char boundary
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, ca), cb)| ca != cb)
+        .map_or_else(|| a.len().min(b.len()), |((i, _), _)| i)
+}
+
+fn algorithm_code(algorithm: HashAlgorithm) -> u8 {
+    match algorithm {
+        HashAlgorithm::Sha256 => 0,
+        HashAlgorithm::Blake3 => 1,
+        HashAlgorithm::Xxh3 => 2,
+        HashAlgorithm::GitSha1 => 3,
+        HashAlgorithm::GitSha256 => 4,
+    }
+}
+
+fn algorithm_from_code(code: u8) -> Option<HashAlgorithm> {
+    match code {
+        0 => Some(HashAlgorithm::Sha256),
+        1 => Some(HashAlgorithm::Blake3),
+        2 => Some(HashAlgorithm::Xxh3),
+        3 => Some(HashAlgorithm::GitSha1),
+        4 => Some(HashAlgorithm::GitSha256),
+        _ => None,
+    }
+}
+
+/// The `HashAlgorithm` string as seen from JS
+fn algorithm_name(algorithm: HashAlgorithm) -> &'static str {
+    match algorithm {
+        HashAlgorithm::Sha256 => "sha256",
+        HashAlgorithm::Blake3 => "blake3",
+        HashAlgorithm::Xxh3 => "xxh3",
+        HashAlgorithm::GitSha1 => "gitSha1",
+        HashAlgorithm::GitSha256 => "gitSha256",
+    }
+}
+
+fn algorithm_from_name(name: &str) -> Option<HashAlgorithm> {
+    match name {
+        "sha256" => Some(HashAlgorithm::Sha256),
+        "blake3" => Some(HashAlgorithm::Blake3),
+        "xxh3" => Some(HashAlgorithm::Xxh3),
+        "gitSha1" => Some(HashAlgorithm::GitSha1),
+        "gitSha256" => Some(HashAlgorithm::GitSha256),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::{build_merkle_tree, FileHashEntry, MerkleOptions};
+
+    fn tree(files: &[&str], hash_scheme: u32, namespace: Option<&str>) -> Vec<MerkleNode> {
+        tree_with_sizes(files, &[], hash_scheme, namespace)
+    }
+
+    /// File `i` gets `sizes[i]`, when given
+    fn tree_with_sizes(
+        files: &[&str],
+        sizes: &[i64],
+        hash_scheme: u32,
+        namespace: Option<&str>,
+    ) -> Vec<MerkleNode> {
+        let files = files
+            .iter()
+            .enumerate()
+            .map(|(i, path)| FileHashEntry {
+                path: path.to_string(),
+                hash: format!("{:064x}", i + 1),
+                is_directory: None,
+                size: sizes.get(i).copied(),
+            })
+            .collect();
+        let options = MerkleOptions {
+            hash_scheme: Some(hash_scheme),
+            namespace: namespace.map(str::to_string),
+            ..Default::default()
+        };
+        build_merkle_tree(files, Some(options)).unwrap()
+    }
+
+    type NodeSummary = (String, String, bool, Vec<String>, Option<i64>, Option<u32>);
+
+    fn sorted(nodes: Vec<MerkleNode>) -> Vec<NodeSummary> {
+        let mut nodes: Vec<_> = nodes
+            .into_iter()
+            .map(|n| {
+                let mut children = n.children;
+                children.sort();
+                (n.path, n.hash, n.is_file, children, n.size, n.file_count)
+            })
+            .collect();
+        nodes.sort();
+        nodes
+    }
+
+    fn assert_round_trip(nodes: Vec<MerkleNode>, version: u8) {
+        let bytes = serialize_tree(nodes.clone()).unwrap();
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(bytes[4], version);
+        let decoded = deserialize_tree(bytes).unwrap();
+        assert_eq!(tree_scheme(&decoded).unwrap(), tree_scheme(&nodes).unwrap());
+        assert_eq!(tree_namespace(&decoded).unwrap(), tree_namespace(&nodes).unwrap());
+        assert_eq!(sorted(decoded), sorted(nodes.clone()));
+
+        let json = export_tree_json(nodes.clone()).unwrap();
+        assert_eq!(sorted(import_tree_json(json).unwrap()), sorted(nodes));
+    }
+
+    const FILES: [&str; 4] = ["README.md", "src/lib.rs", "src/é/β.rs", "tests/a.rs"];
+
+    #[test]
+    fn legacy_tree_round_trips_as_version_1() {
+        assert_round_trip(tree(&FILES, LEGACY_HASH_SCHEME, None), FORMAT_VERSION);
+    }
+
+    #[test]
+    fn named_tree_round_trips_as_version_2() {
+        assert_round_trip(tree(&FILES, NAMED_HASH_SCHEME, None), FORMAT_VERSION_SCHEME);
+    }
+
+    #[test]
+    fn namespaced_tree_round_trips_as_version_3() {
+        let nodes = tree(&FILES, NAMED_HASH_SCHEME, Some("workspace-1"));
+        assert_round_trip(nodes, FORMAT_VERSION_NAMESPACE);
+    }
+
+    #[test]
+    fn sized_tree_round_trips_as_version_4() {
+        let sizes = [12, 0x0001_0000_0000, 7, 3];
+        assert_round_trip(
+            tree_with_sizes(&FILES, &sizes, LEGACY_HASH_SCHEME, None),
+            FORMAT_VERSION_SIZES,
+        );
+        let nodes = tree_with_sizes(&FILES, &sizes, NAMED_HASH_SCHEME, Some("workspace-1"));
+        assert_round_trip(nodes, FORMAT_VERSION_SIZES);
+    }
+
+    #[test]
+    fn partly_sized_tree_keeps_directory_totals() {
+        let nodes = tree_with_sizes(&FILES, &[12, 30], NAMED_HASH_SCHEME, None);
+        let src = nodes.iter().find(|n| n.path == "src").unwrap();
+        assert_eq!((src.size, src.file_count), (Some(30), Some(2)));
+        let tests = nodes.iter().find(|n| n.path == "tests").unwrap();
+        assert_eq!((tests.size, tests.file_count), (None, Some(1)));
+        assert_round_trip(nodes, FORMAT_VERSION_SIZES);
+    }
+
+    #[test]
+    fn mapped_diff_enters_only_changed_directories() {
+        let old = tree(
+            &["a/x.rs", "a/y.rs", "b/deep/z.rs", "c/k.rs", "d", "e/f.rs"],
+            NAMED_HASH_SCHEME,
+            None,
+        );
+        let mut new = tree(
+            &["a/x.rs", "a/y.rs", "b/deep/z.rs", "c", "d/m.rs", "e/g.rs", "n/o.rs"],
+            NAMED_HASH_SCHEME,
+            None,
+        );
+        // a/ keeps its hashes, b/deep/z.rs changes, e/f.rs moves to e/g.rs
+        let old_hash = |path: &str| old.iter().find(|n| n.path == path).unwrap().hash.clone();
+        let moved = [("b/deep/z.rs", "f".repeat(64)), ("e/g.rs", old_hash("e/f.rs"))];
+        let files: Vec<FileHashEntry> = new
+            .iter()
+            .filter(|n| n.is_file)
+            .map(|n| FileHashEntry {
+                path: n.path.clone(),
+                hash: moved
+                    .iter()
+                    .find(|(path, _)| *path == n.path)
+                    .map_or_else(|| n.hash.clone(), |(_, hash)| hash.clone()),
+                is_directory: None,
+                size: None,
+            })
+            .collect();
+        let options = MerkleOptions {
+            hash_scheme: Some(NAMED_HASH_SCHEME),
+            ..Default::default()
+        };
+        new = build_merkle_tree(files, Some(options)).unwrap();
+
+        let path = std::env::temp_dir()
+            .join(format!("codebase-native-test-walk-{}.cnmt", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        save_tree(path.clone(), old.clone()).unwrap();
+        let mapped = open_tree(path.clone()).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let mut sorted_new: Vec<&MerkleNode> = new.iter().collect();
+        sorted_new.sort_by(|a, b| a.path.cmp(&b.path));
+        let (old_kept, _) = mapped.differing_nodes(&sorted_new).unwrap();
+        let kept: Vec<&str> = old_kept.iter().map(|n| n.path.as_str()).collect();
+        assert!(!kept.iter().any(|p| p.starts_with('a')), "{:?}", kept);
+        assert!(kept.contains(&"b/deep/z.rs"));
+
+        let options = DiffOptions {
+            include_directories: Some(true),
+            detect_renames: Some(true),
+        };
+        let walked = mapped.diff(new.clone(), Some(options.clone())).unwrap();
+        let flat = diff_merkle_trees(old, new, Some(options)).unwrap();
+        assert_eq!(walked.added, flat.added);
+        assert_eq!(walked.removed, flat.removed);
+        assert_eq!(walked.modified, flat.modified);
+        assert_eq!(walked.changed_directories, flat.changed_directories);
+        let renames = |diff: &MerkleDiff| -> Vec<(String, String)> {
+            let renamed = diff.renamed.as_ref().unwrap();
+            renamed.iter().map(|r| (r.from.clone(), r.to.clone())).collect()
+        };
+        assert_eq!(renames(&walked), renames(&flat));
+        assert!(renames(&flat).contains(&("e/f.rs".to_string(), "e/g.rs".to_string())));
+        assert_eq!(flat.modified, vec!["b/deep/z.rs"]);
+        assert_eq!(flat.added, vec!["n/o.rs"]);
+    }
+
+    #[test]
+    fn mapped_legacy_tree_reports_rename() {
+        let path = std::env::temp_dir()
+            .join(format!("codebase-native-test-{}.cnmt", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        save_tree(path.clone(), tree(&["a.txt"], LEGACY_HASH_SCHEME, None)).unwrap();
+        let mapped = open_tree(path.clone()).unwrap();
+        let diff = mapped.diff(tree(&["b.txt"], LEGACY_HASH_SCHEME, None), None);
+        drop(mapped);
+        let _ = fs::remove_file(&path);
+
+        let diff = diff.unwrap();
+        assert_eq!(diff.added, vec!["b.txt"]);
+        assert_eq!(diff.removed, vec!["a.txt"]);
+    }
+}
@@ -0,0 +1,412 @@
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+
+/// Options for `similarity_matrix`
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct SimilarityOptions {
+    /// Return only the `top_k` most similar columns per row instead of the full matrix
+    pub top_k: Option<u32>,
+}
+
+/// Best matches of one row of `a`, most similar first
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct RowMatches {
+    /// Row indices into `b`
+    pub indices: Vec<u32>,
+    pub scores: Vec<f64>,
+}
+
+/// Result of `similarity_matrix`
+#[napi(object)]
+pub struct SimilarityResult {
+    /// Number of vectors in `a`
+    pub rows: u32,
+    /// Number of vectors in `b`
+    pub cols: u32,
+    /// Row-major `rows x cols` little-endian f32 scores (without `top_k`)
+    pub scores: Option<Buffer>,
+    /// Best matches per row (with `top_k`)
+    pub top: Option<Vec<RowMatches>>,
+}
+
+/// Cosine similarity of every vector in `a` with every vector in `b`. Both are
+/// packed little-endian f32 vectors of `dim` components, e.g. the bytes of a
+/// `Float32Array`. Rows run in parallel; zero vectors score 0 against anything.
+#[napi(catch_unwind)]
+pub fn similarity_matrix(
+    a: Buffer,
+    b: Buffer,
+    dim: u32,
+    options: Option<SimilarityOptions>,
+) -> napi::Result<SimilarityResult> {
+    use rayon::prelude::*;
+
+    let a = Embeddings::parse(&a, dim, "a")?;
+    let b = Embeddings::parse(&b, dim, "b")?;
+    let row_scores =
+        |i: usize| -> Vec<f32> { (0..b.len()).map(|j| cosine(&a, i, &b, j)).collect() };
+
+    let top = match options.unwrap_or_default().top_k {
+        Some(k) => k as usize,
+        None => {
+            let scores: Vec<u8> = (0..a.len())
+                .into_par_iter()
+                .flat_map_iter(|i| row_scores(i).into_iter().flat_map(f32::to_le_bytes))
+                .collect();
+            return Ok(SimilarityResult {
+                rows: a.len() as u32,
+                cols: b.len() as u32,
+                scores: Some(scores.into()),
+                top: None,
+            });
+        }
+    };
+    let top = (0..a.len())
+        .into_par_iter()
+        .map(|i| {
+            let mut ranked: Vec<(u32, f32)> = row_scores(i)
+                .into_iter()
+                .enumerate()
+                .map(|(j, s)| (j as u32, s))
+                .collect();
+            let k = top.min(ranked.len());
+            if k < ranked.len() {
+                ranked.select_nth_unstable_by(k, |x, y| y.1.total_cmp(&x.1));
+                ranked.truncate(k);
+            }
+            ranked.sort_by(|x, y| y.1.total_cmp(&x.1).then(x.0.cmp(&y.0)));
+            RowMatches {
+                indices: ranked.iter().map(|&(j, _)| j).collect(),
+                scores: ranked.iter().map(|&(_, s)| s as f64).collect(),
+            }
+        })
+        .collect();
+    Ok(SimilarityResult {
+        rows: a.len() as u32,
+        cols: b.len() as u32,
+        scores: None,
+        top: Some(top),
+    })
+}
+
+/// Options for `cluster_embeddings`; at most one of `k` and `threshold` may be
+/// given, and without either k is √(n/2) for n vectors
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct ClusterOptions {
+    /// Number of clusters (capped at the number of vectors)
+    pub k: Option<u32>,
+    /// Minimum cosine similarity to a cluster's leader for a vector to join it;
+    /// the number of clusters follows from the data
+    pub threshold: Option<f64>,
+    /// Refinement rounds, default 50; stops early once no vector moves
+    pub max_iterations: Option<u32>,
+    /// Representatives reported per cluster, default 3
+    pub representatives: Option<u32>,
+    /// Seed for the k-means++ initialisation, so results are reproducible
+    pub seed: Option<u32>,
+}
+
+/// One cluster of `cluster_embeddings`
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct Cluster {
+    pub size: u32,
+    /// Members closest to the centroid, closest first
+    pub representatives: Vec<u32>,
+    /// Mean cosine similarity of the members to the centroid
+    pub cohesion: f64,
+}
+
+/// Result of `cluster_embeddings`
+#[napi(object)]
+pub struct ClusterResult {
+    /// Cluster index of every vector
+    pub assignments: Vec<u32>,
+    /// Clusters, largest first
+    pub clusters: Vec<Cluster>,
+    /// Refinement rounds run
+    pub iterations: u32,
+}
+
+/// Group packed little-endian f32 vectors (as for `similarity_matrix`) by
+/// cosine similarity with spherical k-means. With `threshold` the initial
+/// centroids come from a single leader pass instead of k-means++.
+#[napi(catch_unwind)]
+pub fn cluster_embeddings(
+    vectors: Buffer,
+    dim: u32,
+    options: Option<ClusterOptions>,
+) -> napi::Result<ClusterResult> {
+    use rayon::prelude::*;
+
+    let options = options.unwrap_or_default();
+    let vectors = Embeddings::parse(&vectors, dim, "vectors")?.normalized();
+    let n = vectors.len();
+    let seed = options.seed.unwrap_or(0);
+    let mut centroids = match (options.k, options.threshold) {
+        (Some(0), None) => return Err(napi::Error::from_reason("k must be at least 1")),
+        (Some(k), None) => seed_centroids(&vectors, k as usize, seed),
+        (None, Some(threshold)) => leader_centroids(&vectors, threshold as f32),
+        (None, None) => {
+            let k = ((n as f64 / 2.0).sqrt().round() as usize).max(1);
+            seed_centroids(&vectors, k, seed)
+        }
+        (Some(_), Some(_)) => {
+            return Err(napi::Error::from_reason(
+                "At most one of k and threshold may be given",
+            ))
+        }
+    };
+
+    let mut assignments: Vec<(usize, f32)> = Vec::new();
+    let mut iterations = 0;
+    for _ in 0..options.max_iterations.unwrap_or(50) {
+        let next: Vec<(usize, f32)> =
+            (0..n).into_par_iter().map(|i| nearest(&centroids, vectors.row(i))).collect();
+        let moved = assignments.len() != n
+            || next.iter().zip(&assignments).any(|(a, b)| a.0 != b.0);
+        assignments = next;
+        if !moved {
+            break;
+        }
+        iterations += 1;
+        centroids = recompute_centroids(&vectors, &assignments, centroids);
+    }
+    if assignments.len() != n {
+        // max_iterations of 0: assign without refining
+        assignments = (0..n).map(|i| nearest(&centroids, vectors.row(i))).collect();
+    }
+
+    // Drop empty clusters and number the rest by descending size
+    let mut members: Vec<Vec<(u32, f32)>> = vec![Vec::new(); centroids.len()];
+    for (i, &(c, score)) in assignments.iter().enumerate() {
+        members[c].push((i as u32, score));
+    }
+    let mut order: Vec<usize> = (0..members.len()).filter(|&c| !members[c].is_empty()).collect();
+    order.sort_by(|&x, &y| members[y].len().cmp(&members[x].len()).then(x.cmp(&y)));
+    let mut renumbered = vec![0u32; members.len()];
+    for (new, &old) in order.iter().enumerate() {
+        renumbered[old] = new as u32;
+    }
+
+    let representatives = options.representatives.unwrap_or(3) as usize;
+    let clusters = order
+        .iter()
+        .map(|&c| {
+            let mut ranked = members[c].clone();
+            let cohesion = ranked.iter().map(|&(_, s)| s as f64).sum::<f64>() / ranked.len() as f64;
+            ranked.sort_by(|x, y| y.1.total_cmp(&x.1).then(x.0.cmp(&y.0)));
+            Cluster {
+                size: ranked.len() as u32,
+                representatives: ranked.iter().take(representatives).map(|&(i, _)| i).collect(),
+                cohesion,
+            }
+        })
+        .collect();
+    Ok(ClusterResult {
+        assignments: assignments.iter().map(|&(c, _)| renumbered[c]).collect(),
+        clusters,
+        iterations,
+    })
+}
+
+/// k-means++: each further centroid is drawn with probability proportional to
+/// the squared cosine distance from the closest centroid so far
+fn seed_centroids(vectors: &Embeddings, k: usize, seed: u32) -> Vec<Vec<f32>> {
+    let n = vectors.len();
+    let mut state = 0x9E37_79B9_7F4A_7C15u64 ^ seed as u64;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let mut centroids: Vec<Vec<f32>> = Vec::new();
+    if n == 0 {
+        return centroids;
+    }
+    let mut distances = vec![f64::INFINITY; n];
+    let mut next = (random() * n as f64) as usize;
+    while centroids.len() < k.min(n) {
+        centroids.push(vectors.row(next).to_vec());
+        let centroid = centroids.last().unwrap();
+        for (i, d) in distances.iter_mut().enumerate() {
+            let distance = (1.0 - dot(vectors.row(i), centroid) as f64).max(0.0);
+            *d = d.min(distance * distance);
+        }
+        let total: f64 = distances.iter().sum();
+        if total == 0.0 {
+            // Fewer distinct vectors than k
+            break;
+        }
+        let mut target = random() * total;
+        next = distances
+            .iter()
+            .position(|&d| {
+                target -= d;
+                target < 0.0
+            })
+            .unwrap_or(n - 1);
+    }
+    centroids
+}
+
+/// A vector joins the first leader it is at least `threshold` similar to, or
+/// becomes a leader itself
+fn leader_centroids(vectors: &Embeddings, threshold: f32) -> Vec<Vec<f32>> {
+    let mut leaders: Vec<Vec<f32>> = Vec::new();
+    for i in 0..vectors.len() {
+        let row = vectors.row(i);
+        if !leaders.iter().any(|l| dot(l, row) >= threshold) {
+            leaders.push(row.to_vec());
+        }
+    }
+    leaders
+}
+
+fn nearest(centroids: &[Vec<f32>], row: &[f32]) -> (usize, f32) {
+    centroids
+        .iter()
+        .enumerate()
+        .map(|(c, centroid)| (c, dot(centroid, row)))
+        .fold((0, f32::NEG_INFINITY), |best, x| if x.1 > best.1 { x } else { best })
+}
+
+/// Normalised mean of each cluster's members; clusters left empty keep their
+/// previous centroid
+fn recompute_centroids(
+    vectors: &Embeddings,
+    assignments: &[(usize, f32)],
+    mut centroids: Vec<Vec<f32>>,
+) -> Vec<Vec<f32>> {
+    let mut sums = vec![vec![0f32; vectors.dim]; centroids.len()];
+    for (i, &(c, _)) in assignments.iter().enumerate() {
+        for (sum, x) in sums[c].iter_mut().zip(vectors.row(i)) {
+            *sum += x;
+        }
+    }
+    for (centroid, sum) in centroids.iter_mut().zip(sums) {
+        let norm = dot(&sum, &sum).sqrt();
+        if norm > 0.0 {
+            *centroid = sum.into_iter().map(|x| x / norm).collect();
+        }
+    }
+    centroids
+}
+
+/// Packed f32 vectors with their precomputed norms
+pub(crate) struct Embeddings {
+    pub values: Vec<f32>,
+    pub norms: Vec<f32>,
+    pub dim: usize,
+}
+
+impl Embeddings {
+    pub(crate) fn parse(bytes: &[u8], dim: u32, name: &str) -> napi::Result<Self> {
+        let dim = dim as usize;
+        if dim == 0 || !bytes.len().is_multiple_of(dim * 4) {
+            return Err(napi::Error::from_reason(format!(
+                "{} holds {} bytes, not a whole number of {}-dimensional f32 vectors",
+                name,
+                bytes.len(),
+                dim
+            )));
+        }
+        let values: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let norms = values.chunks_exact(dim).map(|v| dot(v, v).sqrt()).collect();
+        Ok(Embeddings { values, norms, dim })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.norms.len()
+    }
+
+    pub(crate) fn row(&self, i: usize) -> &[f32] {
+        &self.values[i * self.dim..(i + 1) * self.dim]
+    }
+
+    /// The same vectors scaled to unit length; zero vectors stay zero
+    pub(crate) fn normalized(self) -> Self {
+        let dim = self.dim;
+        let values = self
+            .values
+            .chunks_exact(dim)
+            .zip(&self.norms)
+            .flat_map(|(v, &norm)| v.iter().map(move |x| if norm > 0.0 { x / norm } else { 0.0 }))
+            .collect();
+        let norms = self.norms.iter().map(|&n| if n > 0.0 { 1.0 } else { 0.0 }).collect();
+        Embeddings { values, norms, dim }
+    }
+}
+
+fn cosine(a: &Embeddings, i: usize, b: &Embeddings, j: usize) -> f32 {
+    let norms = a.norms[i] * b.norms[j];
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot(a.row(i), b.row(j)) / norms
+    }
+}
+
+/// Dot product in plain scalar code. The eight independent accumulators break
+/// the dependency chain of a single running sum, which lets the optimizer
+/// vectorize the loop but does not guarantee it.
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let mut lanes = [0f32; 8];
+    let (a_chunks, b_chunks) = (a.chunks_exact(8), b.chunks_exact(8));
+    let tail: f32 = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(x, y)| x * y)
+        .sum();
+    for (x, y) in a_chunks.zip(b_chunks) {
+        for lane in 0..8 {
+            lanes[lane] += x[lane] * y[lane];
+        }
+    }
+    lanes.iter().sum::<f32>() + tail
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(vectors: &[[f32; 2]]) -> Buffer {
+        let bytes: Vec<u8> = vectors.iter().flatten().flat_map(|x| x.to_le_bytes()).collect();
+        bytes.into()
+    }
+
+    #[test]
+    fn clustering_without_options_picks_k_from_the_data() {
+        let vectors = [
+            [1.0, 0.0],
+            [0.99, 0.05],
+            [0.98, 0.1],
+            [0.97, 0.02],
+            [0.0, 1.0],
+            [0.05, 0.99],
+            [0.1, 0.98],
+            [0.02, 0.97],
+        ];
+        let result = cluster_embeddings(pack(&vectors), 2, None).unwrap();
+        assert_eq!(result.clusters.len(), 2);
+        let a = &result.assignments;
+        assert!(a[..4].iter().all(|&c| c == a[0]));
+        assert!(a[4..].iter().all(|&c| c == a[4]));
+        assert_ne!(a[0], a[4]);
+
+        let both = ClusterOptions {
+            k: Some(2),
+            threshold: Some(0.5),
+            ..Default::default()
+        };
+        assert!(cluster_embeddings(pack(&vectors), 2, Some(both)).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use napi_derive::napi;
+
+/// Bumped whenever an exported function or object changes incompatibly
+const API_LEVEL: u32 = 1;
+
+/// Version of the loaded native module
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct NativeVersion {
+    /// Crate version
+    pub version: String,
+    /// Compatibility level of the exported API
+    pub api_level: u32,
+    /// Target OS and architecture the module was compiled for, e.g. `linux-x86_64`
+    pub target: String,
+}
+
+/// Optional subsystems compiled into this build
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct FeatureFlags {
+    /// Content-defined chunking (`cdc` cargo feature)
+    pub cdc: bool,
+    /// zstd compression helpers (`zstd` cargo feature)
+    pub zstd: bool,
+    /// `posix_fadvise` read-ahead for hashing (Linux only)
+    pub read_ahead: bool,
+}
+
+#[napi(catch_unwind)]
+pub fn get_native_version() -> NativeVersion {
+    NativeVersion {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        api_level: API_LEVEL,
+        target: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+    }
+}
+
+/// Which optional subsystems this build provides, so callers can adapt up front
+/// instead of failing at call time
+#[napi(catch_unwind)]
+pub fn get_feature_flags() -> FeatureFlags {
+    FeatureFlags {
+        cdc: cfg!(feature = "cdc"),
+        zstd: cfg!(feature = "zstd"),
+        read_ahead: cfg!(target_os = "linux"),
+    }
+}
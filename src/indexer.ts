@@ -33,7 +33,7 @@ import { EmbeddingCache } from './storage/cache.js';
 // For development, we provide TS fallbacks
 let native: {
   scanDirectory?: (rootPath: string, extensions: string[]) => string[];
-  sha256HashFiles?: (filePaths: string[]) => Array<{ path: string; hash: string }>;
+  sha256HashFiles?: (filePaths: string[]) => { hashes: Array<{ path: string; hash: string }> };
   buildMerkleTree?: (fileHashes: FileHashEntry[]) => MerkleNode[];
   diffMerkleTrees?: (oldNodes: MerkleNode[], newNodes: MerkleNode[]) => { added: string[]; removed: string[]; modified: string[] };
   getRootHash?: (nodes: MerkleNode[]) => string | null;
@@ -103,9 +103,9 @@ function tsBuildMerkleTree(
     }
   }
 
-  // Build directory nodes bottom-up
+  // Build directory nodes bottom-up; the root is hashed last
   const dirPaths = [...dirChildren.keys()].sort(
-    (a, b) => b.split('/').length - a.split('/').length
+    (a, b) => tsDirDepth(b) - tsDirDepth(a)
   );
 
   for (const dirPath of dirPaths) {
@@ -163,6 +163,10 @@ function tsDiffMerkleTrees(
   return { added, removed, modified };
 }
 
+function tsDirDepth(dirPath: string): number {
+  return dirPath === '.' ? 0 : dirPath.split('/').length;
+}
+
 function tsParentPath(p: string): string {
   const idx = p.lastIndexOf('/');
   return idx >= 0 ? p.substring(0, idx) : '.';
@@ -485,7 +489,7 @@ export class Indexer {
 
   private hashFiles(filePaths: string[]): FileHashEntry[] {
     const hashes = native?.sha256HashFiles
-      ? native.sha256HashFiles(filePaths)
+      ? native.sha256HashFiles(filePaths).hashes
       : tsSha256HashFiles(filePaths);
 
     return hashes.map((h) => ({